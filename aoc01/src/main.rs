@@ -1,69 +1,385 @@
-fn find_patterns(lines: &Vec<String>, patterns: &Vec<(&str, i32)>) -> i32 {
-    let mut sum = 0;
-    for line in lines {
-        let mut firstoffset = None;
-        let mut lastoffset = None;
-        let mut first = 0;
-        let mut last = 0;
-        for (pat, val) in patterns.iter() {
-            if let Some(offset) = line.find(pat) {
-                if firstoffset.is_none() || offset < firstoffset.unwrap() {
-                    firstoffset = Some(offset);
-                    first = *val;
-                }
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{anyhow, Result};
+use util::input_lines;
+
+const DIGITS: [(&str, i32); 10] = [
+    ("0", 0),
+    ("1", 1),
+    ("2", 2),
+    ("3", 3),
+    ("4", 4),
+    ("5", 5),
+    ("6", 6),
+    ("7", 7),
+    ("8", 8),
+    ("9", 9),
+];
+
+const WORDS: [(&str, i32); 10] = [
+    ("zero", 0),
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Match {
+    // Byte offset of the start of the match.
+    offset: usize,
+    len: usize,
+    val: i32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    next: HashMap<u8, usize>,
+    // The longest proper suffix of this node's string that is also in the trie.
+    fail: usize,
+    // Patterns ending here, including those ending at any suffix.
+    out: Vec<(usize, i32)>,
+}
+
+// An Aho-Corasick automaton over a table of patterns, which finds every
+// occurrence of every pattern, overlapping or not, in one pass over a line.
+#[derive(Debug, Clone)]
+struct Matcher {
+    nodes: Vec<Node>,
+}
+
+impl Matcher {
+    fn new(patterns: &[(&str, i32)]) -> Result<Matcher> {
+        let mut nodes = vec![Node::default()];
+        for (pat, val) in patterns {
+            if pat.is_empty() {
+                return Err(anyhow!("Empty pattern for {}", val));
             }
-            if let Some(offset) = line.rfind(pat) {
-                if lastoffset.is_none() || offset > lastoffset.unwrap() {
-                    lastoffset = Some(offset);
-                    last = *val;
-                }
+            let mut node = 0;
+            for b in pat.bytes() {
+                node = match nodes[node].next.get(&b) {
+                    Some(n) => *n,
+                    None => {
+                        let n = nodes.len();
+                        nodes.push(Node::default());
+                        nodes[node].next.insert(b, n);
+                        n
+                    }
+                };
+            }
+            nodes[node].out.push((pat.len(), *val));
+        }
+        // Fail links, breadth first so that each node's fail target is done
+        // before the node itself.
+        let mut queue = VecDeque::from([0]);
+        while let Some(node) = queue.pop_front() {
+            let next = nodes[node].next.clone();
+            for (b, child) in next {
+                let fail = if node == 0 {
+                    0
+                } else {
+                    Matcher::step(&nodes, nodes[node].fail, b)
+                };
+                nodes[child].fail = fail;
+                let inherited = nodes[fail].out.clone();
+                nodes[child].out.extend(inherited);
+                queue.push_back(child);
             }
         }
-        sum += 10 * first + last;
+        Ok(Matcher { nodes })
     }
-    sum
+
+    fn step(nodes: &[Node], mut node: usize, b: u8) -> usize {
+        loop {
+            if let Some(n) = nodes[node].next.get(&b) {
+                return *n;
+            }
+            if node == 0 {
+                return 0;
+            }
+            node = nodes[node].fail;
+        }
+    }
+
+    // Every match in the line, in order of where it ends.
+    fn matches(&self, line: &str) -> Vec<Match> {
+        let mut matches = Vec::new();
+        let mut node = 0;
+        for (i, b) in line.bytes().enumerate() {
+            node = Matcher::step(&self.nodes, node, b);
+            for (len, val) in &self.nodes[node].out {
+                matches.push(Match {
+                    offset: i + 1 - len,
+                    len: *len,
+                    val: *val,
+                });
+            }
+        }
+        matches
+    }
+
+    // The first and last matches in the line. When two matches start at the
+    // same place, the longer one wins, so "nineteen" beats "nine".
+    fn first_last(&self, line: &str) -> Option<(Match, Match)> {
+        let matches = self.matches(line);
+        let first = matches.iter().min_by_key(|m| (m.offset, Reverse(m.len)))?;
+        let last = matches.iter().max_by_key(|m| (m.offset, m.len))?;
+        Some((*first, *last))
+    }
+}
+
+fn find_patterns(lines: &[String], patterns: &[(&str, i32)]) -> Result<i32> {
+    let matcher = Matcher::new(patterns)?;
+    let mut sum = 0;
+    for (n, line) in lines.iter().enumerate() {
+        let (first, last) = matcher
+            .first_last(line)
+            .ok_or_else(|| anyhow!("No digit on line {}: {:?}", n + 1, line))?;
+        sum += 10 * first.val + last.val;
+    }
+    Ok(sum)
 }
 
-fn main() {
-    let lines = std::io::stdin()
-        .lines()
-        .map(|r| r.unwrap())
-        .collect::<Vec<_>>();
-
-    let patterns = vec![
-        ("0", 0),
-        ("1", 1),
-        ("2", 2),
-        ("3", 3),
-        ("4", 4),
-        ("5", 5),
-        ("6", 6),
-        ("7", 7),
-        ("8", 8),
-        ("9", 9),
-    ];
-    println!("Part 1: {}", find_patterns(&lines, &patterns));
-    let patterns = vec![
-        ("0", 0),
-        ("1", 1),
-        ("2", 2),
-        ("3", 3),
-        ("4", 4),
-        ("5", 5),
-        ("6", 6),
-        ("7", 7),
-        ("8", 8),
-        ("9", 9),
-        ("zero", 0),
-        ("one", 1),
-        ("two", 2),
-        ("three", 3),
-        ("four", 4),
-        ("five", 5),
-        ("six", 6),
-        ("seven", 7),
-        ("eight", 8),
-        ("nine", 9),
-    ];
-    println!("Part 2: {}", find_patterns(&lines, &patterns));
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const MAGENTA: &str = "\x1b[35m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+// For each line, every match and its byte offset, then the first and last
+// values and the calibration value. Lines without a digit are reported rather
+// than failing. With `color`, the first match is shown in green, the last in
+// red, bytes in both in magenta and other matches in yellow.
+fn report(lines: &[String], patterns: &[(&str, i32)], color: bool) -> Result<String> {
+    let matcher = Matcher::new(patterns)?;
+    let mut out = String::new();
+    let mut sum = 0;
+    for (n, line) in lines.iter().enumerate() {
+        let matches = matcher.matches(line);
+        let first_last = matcher.first_last(line);
+        let shown = if color {
+            highlight(line, &matches, first_last)
+        } else {
+            line.clone()
+        };
+        out += &format!("Line {}: {}\n", n + 1, shown);
+        for m in &matches {
+            out += &format!(
+                "  {:>3} {:?} = {}\n",
+                m.offset,
+                &line[m.offset..m.offset + m.len],
+                m.val
+            );
+        }
+        match first_last {
+            Some((first, last)) => {
+                let value = 10 * first.val + last.val;
+                sum += value;
+                out += &format!(
+                    "  first {}, last {}, value {}\n",
+                    first.val, last.val, value
+                );
+            }
+            None => out += "  no digit\n",
+        }
+    }
+    out += &format!("Sum: {}\n", sum);
+    Ok(out)
+}
+
+fn highlight(line: &str, matches: &[Match], first_last: Option<(Match, Match)>) -> String {
+    let within = |m: &Match, i: usize| (m.offset..m.offset + m.len).contains(&i);
+    let mut out = String::new();
+    let mut current = "";
+    for (i, c) in line.char_indices() {
+        let code = match first_last {
+            Some((f, l)) if within(&f, i) && within(&l, i) => MAGENTA,
+            Some((f, _)) if within(&f, i) => GREEN,
+            Some((_, l)) if within(&l, i) => RED,
+            _ if matches.iter().any(|m| within(m, i)) => YELLOW,
+            _ => "",
+        };
+        if code != current {
+            out += if code.is_empty() { RESET } else { code };
+            current = code;
+        }
+        out.push(c);
+    }
+    if !current.is_empty() {
+        out += RESET;
+    }
+    out
+}
+
+// A word table with one "word value" pair per line.
+fn parse_table(text: &str) -> Result<Vec<(&str, i32)>> {
+    text.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let (word, val) = l
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("Expected \"word value\", got {:?}", l))?;
+            Ok((word, val.trim().parse()?))
+        })
+        .collect()
+}
+
+fn main() -> Result<()> {
+    let lines = input_lines();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let flag = |f: &str| args.iter().any(|a| a == f);
+    // An optional file of words to use for part 2 instead of English.
+    let table = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(path) => Some(std::fs::read_to_string(path)?),
+        None => None,
+    };
+    let words = match &table {
+        Some(table) => parse_table(table)?,
+        None => WORDS.to_vec(),
+    };
+    if flag("--report") {
+        print!(
+            "{}",
+            report(&lines, &[&DIGITS[..], &words].concat(), flag("--color"))?
+        );
+    }
+    println!("Part 1: {}", find_patterns(&lines, &DIGITS)?);
+    println!(
+        "Part 2: {}",
+        find_patterns(&lines, &[&DIGITS[..], &words].concat())?
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|x| x.to_string()).collect()
+    }
+
+    fn digits_and_words() -> Vec<(&'static str, i32)> {
+        [&DIGITS[..], &WORDS].concat()
+    }
+
+    #[test]
+    fn test_matches() -> Result<()> {
+        let matcher = Matcher::new(&digits_and_words())?;
+        let found = |line| {
+            matcher
+                .matches(line)
+                .iter()
+                .map(|m| (m.offset, m.val))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(found("eightwo"), [(0, 8), (4, 2)]);
+        assert_eq!(found("oneight"), [(0, 1), (2, 8)]);
+        assert_eq!(found("twone3"), [(0, 2), (2, 1), (5, 3)]);
+        assert_eq!(found("sevenine"), [(0, 7), (4, 9)]);
+        assert_eq!(found("xyz"), []);
+        assert!(Matcher::new(&[("", 0)]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tables() -> Result<()> {
+        let teens = parse_table(indoc! {"
+            ten 10
+            eleven 11
+            twelve 12
+            thirteen 13
+            fourteen 14
+            fifteen 15
+            sixteen 16
+            seventeen 17
+            eighteen 18
+            nineteen 19
+        "})?;
+        let table = [&digits_and_words()[..], &teens].concat();
+        assert_eq!(find_patterns(&lines("xnineteen2"), &table)?, 192);
+        assert_eq!(find_patterns(&lines("4seventeen"), &table)?, 57);
+        let german = parse_table("eins 1\nzwei 2\ndrei 3\nvier 4\nfünf 5")?;
+        assert_eq!(find_patterns(&lines("zweifünf"), &german)?, 25);
+        assert_eq!(find_patterns(&lines("xdreinsx"), &german)?, 31);
+        assert!(parse_table("eins").is_err());
+        assert!(parse_table("eins x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_report() -> Result<()> {
+        let report = report(&lines("xtwone3four\nnothing"), &digits_and_words(), false)?;
+        assert_eq!(
+            report,
+            indoc! {r#"
+                Line 1: xtwone3four
+                    1 "two" = 2
+                    3 "one" = 1
+                    6 "3" = 3
+                    7 "four" = 4
+                  first 2, last 4, value 24
+                Line 2: nothing
+                  no digit
+                Sum: 24
+            "#}
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_highlight() -> Result<()> {
+        let matcher = Matcher::new(&digits_and_words())?;
+        let shown = |line| highlight(line, &matcher.matches(line), matcher.first_last(line));
+        assert_eq!(
+            shown("oneight"),
+            format!("{GREEN}on{MAGENTA}e{RED}ight{RESET}")
+        );
+        assert_eq!(
+            shown("oneight5x"),
+            format!("{GREEN}one{YELLOW}ight{RED}5{RESET}x")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_digit() {
+        let err = find_patterns(&lines("1abc2\nnothing"), &DIGITS).unwrap_err();
+        assert_eq!(err.to_string(), "No digit on line 2: \"nothing\"");
+    }
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        let example = lines(indoc! {"
+            1abc2
+            pqr3stu8vwx
+            a1b2c3d4e5f
+            treb7uchet
+        "});
+        assert_eq!(find_patterns(&example, &DIGITS)?, 142);
+        Ok(())
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        let example = lines(indoc! {"
+            two1nine
+            eightwothree
+            abcone2threexyz
+            xtwone3four
+            4nineeightseven2
+            zoneight234
+            7pqrstsixteen
+        "});
+        assert_eq!(find_patterns(&example, &digits_and_words())?, 281);
+        Ok(())
+    }
 }
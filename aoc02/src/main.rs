@@ -1,16 +1,43 @@
-#[derive(Debug)]
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use util::input_lines;
+
+const BAG: &str = "12 red, 13 green, 14 blue";
+
+// A handful of cubes, by color. Also used for the contents of the bag.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 struct Draw {
-    r: u32,
-    g: u32,
-    b: u32,
+    cubes: BTreeMap<String, u32>,
 }
 
 impl Draw {
-    fn new() -> Draw {
-        Draw { r: 0, g: 0, b: 0 }
+    fn parse(s: &str) -> Result<Draw> {
+        let mut d = Draw::default();
+        for count_and_color in s.split(", ") {
+            let (count, color) = count_and_color
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("Expected \"count color\", got {:?}", count_and_color))?;
+            let count = count
+                .parse()
+                .map_err(|e| anyhow!("Bad count in {:?}: {}", count_and_color, e))?;
+            if color.is_empty() || color.contains(char::is_whitespace) {
+                return Err(anyhow!("Bad color in {:?}", count_and_color));
+            }
+            if d.cubes.insert(color.to_string(), count).is_some() {
+                return Err(anyhow!("Color repeated in {:?}", count_and_color));
+            }
+        }
+        Ok(d)
     }
-    fn power(&self) -> u32 {
-        self.r * self.g * self.b
+    fn count(&self, color: &str) -> u32 {
+        self.cubes.get(color).copied().unwrap_or(0)
+    }
+    // None if the product doesn't fit in a u64.
+    fn power(&self) -> Option<u64> {
+        self.cubes
+            .values()
+            .try_fold(1_u64, |p, c| p.checked_mul(*c as u64))
     }
 }
 
@@ -21,62 +48,374 @@ struct Game {
 }
 
 impl Game {
-    fn parse(g: &str) -> Game {
-        let (game, rest) = g.split_once(": ").unwrap();
-        Game {
-            id: game.strip_prefix("Game ").unwrap().parse().unwrap(),
+    fn parse(g: &str) -> Result<Game> {
+        let (game, rest) = g
+            .split_once(": ")
+            .ok_or_else(|| anyhow!("Expected \"Game id: draws\", got {:?}", g))?;
+        let id = game
+            .strip_prefix("Game ")
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(|| anyhow!("Bad game id {:?}", game))?;
+        Ok(Game {
+            id,
             draws: rest
                 .split("; ")
-                .map(|draw| {
-                    let mut d = Draw::new();
-                    for count_and_color in draw.split(", ") {
-                        let (count, color) = count_and_color.split_once(" ").unwrap();
-                        match color {
-                            "red" => d.r = count.parse().unwrap(),
-                            "green" => d.g = count.parse().unwrap(),
-                            "blue" => d.b = count.parse().unwrap(),
-                            _ => panic!("Unrecognized color"),
-                        }
-                    }
-                    d
-                })
-                .collect(),
-        }
+                .map(|draw| Draw::parse(draw).map_err(|e| anyhow!("Game {}: {}", id, e)))
+                .collect::<Result<_>>()?,
+        })
     }
-    fn possible(&self) -> bool {
-        for draw in &self.draws {
-            if draw.r > 12 || draw.g > 13 || draw.b > 14 {
-                return false;
-            }
-        }
-        true
+    // Whether every draw could have come from `bag`. Colors that aren't in
+    // the bag at all can't be drawn.
+    fn possible(&self, bag: &Draw) -> bool {
+        self.draws.iter().all(|draw| {
+            draw.cubes
+                .iter()
+                .all(|(color, count)| *count <= bag.count(color))
+        })
     }
-    fn min_cubes(&self) -> Draw {
-        let mut min = Draw::new();
+    // The fewest cubes of each color that could have been in the bag. Colors
+    // in `bag` that never show up are kept, with a count of 0.
+    fn min_cubes(&self, bag: &Draw) -> Draw {
+        let mut min = Draw {
+            cubes: bag.cubes.keys().map(|c| (c.clone(), 0)).collect(),
+        };
         for draw in &self.draws {
-            min.r = std::cmp::max(draw.r, min.r);
-            min.g = std::cmp::max(draw.g, min.g);
-            min.b = std::cmp::max(draw.b, min.b);
+            for (color, count) in &draw.cubes {
+                let m = min.cubes.entry(color.clone()).or_default();
+                *m = std::cmp::max(*m, *count);
+            }
         }
         min
     }
+    fn power(&self, bag: &Draw) -> Result<u64> {
+        self.min_cubes(bag)
+            .power()
+            .ok_or_else(|| anyhow!("Game {}: power overflows", self.id))
+    }
+    // For each color in `min_cubes`, the index of the first draw that needed
+    // that many cubes, or None if the color was never drawn.
+    fn forced_by(&self, bag: &Draw) -> BTreeMap<String, Option<usize>> {
+        self.min_cubes(bag)
+            .cubes
+            .iter()
+            .map(|(color, min)| {
+                let draw = match min {
+                    0 => None,
+                    _ => self.draws.iter().position(|d| d.count(color) == *min),
+                };
+                (color.clone(), draw)
+            })
+            .collect()
+    }
 }
 
-fn main() {
-    let lines = std::io::stdin()
-        .lines()
-        .map(|r| r.unwrap())
+// How many games have each `min_cubes` power.
+fn power_distribution(games: &[Game], bag: &Draw) -> Result<BTreeMap<u64, usize>> {
+    let mut dist = BTreeMap::new();
+    for game in games {
+        *dist.entry(game.power(bag)?).or_default() += 1;
+    }
+    Ok(dist)
+}
+
+// The bags with at most `total` cubes, and at most `limits` of each color in
+// it, that make the most games possible. Returns that many games, and every
+// bag achieving it that doesn't just add cubes to another such bag.
+//
+// A game is possible exactly when the bag holds at least its `min_cubes`, so
+// each color's count only needs to range over the minimums of the games.
+fn best_bags(games: &[Game], limits: &Draw, total: u32) -> (usize, Vec<Draw>) {
+    let colors = limits.cubes.iter().collect::<Vec<_>>();
+    let mins = games
+        .iter()
+        .map(|g| g.min_cubes(limits))
+        .filter(|m| m.cubes.len() == colors.len())
         .collect::<Vec<_>>();
-    let mut sum_of_ids = 0;
-    let mut power = 0;
-    for line in lines {
-        let game = Game::parse(&line);
-        // println!("{:?}", game);
-        if game.possible() {
-            sum_of_ids += game.id;
+    let mut best = (0, Vec::new());
+    best_bags_from(
+        &colors,
+        mins.iter().collect(),
+        total,
+        &mut Vec::new(),
+        &mut best,
+    );
+    let (count, bags) = best;
+    let dominates = |a: &[u32], b: &[u32]| a != b && a.iter().zip(b).all(|(x, y)| x >= y);
+    let bags = bags
+        .iter()
+        .filter(|a| !bags.iter().any(|b| dominates(a, b)))
+        .map(|counts| Draw {
+            cubes: colors
+                .iter()
+                .zip(counts)
+                .map(|((color, _), count)| (color.to_string(), *count))
+                .collect(),
+        })
+        .collect();
+    (count, bags)
+}
+
+fn best_bags_from(
+    colors: &[(&String, &u32)],
+    alive: Vec<&Draw>,
+    budget: u32,
+    chosen: &mut Vec<u32>,
+    best: &mut (usize, Vec<Vec<u32>>),
+) {
+    if alive.len() < best.0 {
+        return;
+    }
+    let Some(((color, limit), rest)) = colors.split_first() else {
+        if alive.len() > best.0 {
+            *best = (alive.len(), Vec::new());
         }
-        power += game.min_cubes().power();
+        best.1.push(chosen.clone());
+        return;
+    };
+    let mut counts = alive.iter().map(|m| m.count(color)).collect::<Vec<_>>();
+    counts.push(0);
+    counts.sort();
+    counts.dedup();
+    for count in counts {
+        if count > **limit || count > budget {
+            break;
+        }
+        let alive = alive
+            .iter()
+            .filter(|m| m.count(color) <= count)
+            .copied()
+            .collect();
+        chosen.push(count);
+        best_bags_from(rest, alive, budget - count, chosen, best);
+        chosen.pop();
+    }
+}
+
+fn parse(lines: &[String]) -> Result<Vec<Game>> {
+    lines.iter().map(|l| Game::parse(l)).collect()
+}
+
+fn part1(lines: &[String], bag: &Draw) -> Result<u32> {
+    Ok(parse(lines)?
+        .iter()
+        .filter(|g| g.possible(bag))
+        .map(|g| g.id)
+        .sum())
+}
+
+fn part2(lines: &[String], bag: &Draw) -> Result<u64> {
+    let mut sum = 0_u64;
+    for game in parse(lines)? {
+        sum = sum
+            .checked_add(game.power(bag)?)
+            .ok_or_else(|| anyhow!("Sum of powers overflows"))?;
+    }
+    Ok(sum)
+}
+
+fn print_stats(games: &[Game], bag: &Draw) -> Result<()> {
+    println!("Games by power:");
+    for (power, count) in power_distribution(games, bag)? {
+        println!("  {}: {}", power, count);
+    }
+    // The same number of cubes, shared out differently.
+    let total = bag.cubes.values().sum();
+    let limits = Draw {
+        cubes: bag.cubes.keys().map(|c| (c.clone(), total)).collect(),
+    };
+    let (count, bags) = best_bags(games, &limits, total);
+    println!(
+        "Best bags of {} cubes, with {} games possible:",
+        total, count
+    );
+    for bag in bags {
+        let cubes = bag
+            .cubes
+            .iter()
+            .map(|(color, count)| format!("{} {}", count, color))
+            .collect::<Vec<_>>();
+        println!("  {}", cubes.join(", "));
+    }
+    println!("Draws forcing the minimums:");
+    for game in games {
+        let forced = game
+            .forced_by(bag)
+            .iter()
+            .map(|(color, draw)| match draw {
+                Some(d) => format!("{} by draw {}", color, d + 1),
+                None => format!("{} never drawn", color),
+            })
+            .collect::<Vec<_>>();
+        println!("  Game {}: {}", game.id, forced.join(", "));
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let lines = input_lines();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    // The bag's contents can be given like "12 red, 13 green, 14 blue".
+    let bag = Draw::parse(
+        args.iter()
+            .find(|a| !a.starts_with("--"))
+            .map_or(BAG, |a| a.as_str()),
+    )?;
+    println!("Part 1: {}", part1(&lines, &bag)?);
+    println!("Part 2: {}", part2(&lines, &bag)?);
+    if args.iter().any(|a| a == "--stats") {
+        print_stats(&parse(&lines)?, &bag)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|x| x.to_string()).collect()
+    }
+
+    fn example() -> Vec<String> {
+        lines(indoc! {"
+            Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+            Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+            Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+            Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+            Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green
+        "})
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let game = Game::parse("Game 7: 3 blue, 4 red; 2 mauve")?;
+        assert_eq!(game.id, 7);
+        assert_eq!(game.draws[0].count("red"), 4);
+        assert_eq!(game.draws[1].count("mauve"), 2);
+        assert_eq!(game.draws[1].count("red"), 0);
+        let err = |s| Game::parse(s).unwrap_err().to_string();
+        assert_eq!(err("Game x: 1 red"), "Bad game id \"Game x\"");
+        assert_eq!(
+            err("Game 1 1 red"),
+            "Expected \"Game id: draws\", got \"Game 1 1 red\""
+        );
+        assert_eq!(
+            err("Game 3: 1 red; 2 blue, x green"),
+            "Game 3: Bad count in \"x green\": invalid digit found in string"
+        );
+        assert_eq!(
+            err("Game 4: 1 red, 2 red"),
+            "Game 4: Color repeated in \"2 red\""
+        );
+        assert_eq!(
+            err("Game 5: 1 red,2 blue"),
+            "Game 5: Bad color in \"1 red,2 blue\""
+        );
+        assert_eq!(
+            err("Game 6: red"),
+            "Game 6: Expected \"count color\", got \"red\""
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_other_colors() -> Result<()> {
+        let games = lines(indoc! {"
+            Game 1: 2 mauve, 1 teal; 3 teal
+            Game 2: 5 mauve
+            Game 3: 1 red
+        "});
+        let bag = Draw::parse("4 mauve, 3 teal")?;
+        assert_eq!(part1(&games, &bag)?, 1);
+        // Game 2 never shows teal and game 3 shows neither bag color.
+        assert_eq!(part2(&games, &bag)?, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_forced_by() -> Result<()> {
+        let games = parse(&example())?;
+        let bag = Draw::parse(BAG)?;
+        let forced = games[0].forced_by(&bag);
+        assert_eq!(forced["red"], Some(0));
+        assert_eq!(forced["green"], Some(1));
+        assert_eq!(forced["blue"], Some(1));
+        let game = Game::parse("Game 9: 2 red; 2 red, 1 blue")?;
+        let forced = game.forced_by(&bag);
+        assert_eq!(forced["red"], Some(0));
+        assert_eq!(forced["green"], None);
+        assert_eq!(forced["blue"], Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_power_distribution() -> Result<()> {
+        let dist = power_distribution(&parse(&example())?, &Draw::parse(BAG)?)?;
+        assert_eq!(
+            dist,
+            BTreeMap::from([(12, 1), (36, 1), (48, 1), (630, 1), (1560, 1)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_power() -> Result<()> {
+        let bag = Draw::default();
+        let five = lines("Game 1: 100 a, 100 b, 100 c, 100 d, 100 e");
+        assert_eq!(part2(&five, &bag)?, 10_000_000_000);
+        // 100^10 doesn't fit in a u64.
+        let ten =
+            lines("Game 2: 100 a, 100 b, 100 c, 100 d, 100 e; 100 f, 100 g, 100 h, 100 i, 100 j");
+        assert_eq!(
+            part2(&ten, &bag).unwrap_err().to_string(),
+            "Game 2: power overflows"
+        );
+        assert!(power_distribution(&parse(&ten)?, &bag).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_bags() -> Result<()> {
+        let games = parse(&example())?;
+        for total in [0, 6, 10, 20, 30, 39] {
+            let limits = Draw::parse("20 red, 20 green, 20 blue")?;
+            let (count, bags) = best_bags(&games, &limits, total);
+            let mut most = 0;
+            for r in 0..=20 {
+                for g in 0..=20 {
+                    for b in 0..=20 {
+                        if r + g + b > total {
+                            continue;
+                        }
+                        let bag = Draw::parse(&format!("{} red, {} green, {} blue", r, g, b))?;
+                        most = most.max(games.iter().filter(|g| g.possible(&bag)).count());
+                    }
+                }
+            }
+            assert_eq!(count, most, "total {}", total);
+            assert!(!bags.is_empty());
+            for bag in bags {
+                assert!(bag.cubes.values().sum::<u32>() <= total);
+                assert_eq!(games.iter().filter(|g| g.possible(&bag)).count(), count);
+            }
+        }
+        let (count, bags) = best_bags(&games, &Draw::parse("20 red, 13 green, 20 blue")?, 20);
+        assert_eq!(count, 3);
+        assert_eq!(bags, [Draw::parse("6 red, 3 green, 6 blue")?]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&example(), &Draw::parse(BAG)?)?, 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&example(), &Draw::parse(BAG)?)?, 2286);
+        Ok(())
     }
-    println!("Part 1: {}", sum_of_ids);
-    println!("Part 2: {}", power);
 }
@@ -1,119 +1,276 @@
 use std::collections::HashMap;
-use util::Adjacent;
 
-#[derive(Debug)]
+use anyhow::{anyhow, Result};
+use util::input_lines;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Number {
     row: usize,
     col_start: usize,
     col_end: usize,
     val: u32,
 }
-impl Number {
-    fn is_part_number(&self, s: &Schematic) -> bool {
-        // println!("{:?}", self);
-        for c in self.col_start..=self.col_end {
-            for (adj_r, adj_c) in s.raw.adjacent_to(self.row, c) {
-                // println!("{}, {}", adj_r, adj_c);
-                let adj = s.raw[adj_r][adj_c];
-                if !adj.is_ascii_digit() && adj != '.' {
-                    return true;
-                }
-            }
-        }
-        false
-    }
-    fn get_gear(&self, s: &Schematic) -> Option<(usize, usize)> {
-        for c in self.col_start..=self.col_end {
-            for (adj_r, adj_c) in s.raw.adjacent_to(self.row, c) {
-                if s.raw[adj_r][adj_c] == '*' {
-                    return Some((adj_r, adj_c));
-                }
-            }
-        }
-        None
-    }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Symbol {
+    row: usize,
+    col: usize,
+    c: char,
 }
 
 #[derive(Debug)]
 struct Schematic {
-    raw: Vec<Vec<char>>,
     numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    // For each number, the symbols next to it, and for each symbol, the
+    // numbers next to it, as indices into `symbols` and `numbers`.
+    number_symbols: Vec<Vec<usize>>,
+    symbol_numbers: Vec<Vec<usize>>,
 }
 
 impl Schematic {
-    fn from(lines: Vec<String>) -> Schematic {
+    // Columns count chars, not bytes, so a multi-byte symbol takes up one
+    // column like any other. Numbers are runs of ASCII digits, and anything
+    // else other than '.' is a symbol.
+    fn from(lines: &[String]) -> Result<Schematic> {
         let mut numbers = Vec::new();
+        let mut symbols = Vec::new();
+        let mut index = HashMap::new();
         for (row, line) in lines.iter().enumerate() {
             let mut accum = String::from("");
             let mut col_start = 0;
-            let mut col_end = 0;
-            for (col, c) in line.char_indices() {
-                col_end = col; // For handling number at end.
+            // A trailing '.' ends a number at the end of the line.
+            for (col, c) in line.chars().chain(['.']).enumerate() {
                 if c.is_ascii_digit() {
                     if accum.is_empty() {
                         col_start = col;
                     }
                     accum.push(c);
-                } else {
-                    if !accum.is_empty() {
-                        let val: u32 = accum.parse().unwrap();
-                        // println!("Found {}", val);
-                        numbers.push(Number {
-                            row,
-                            col_start,
-                            col_end: col - 1,
-                            val,
-                        });
-                    }
-                    accum = String::from("");
+                    continue;
+                }
+                if !accum.is_empty() {
+                    numbers.push(Number {
+                        row,
+                        col_start,
+                        col_end: col - 1,
+                        val: parse_number(&accum, row)?,
+                    });
+                    accum.clear();
+                }
+                if c != '.' {
+                    index.insert((row, col), symbols.len());
+                    symbols.push(Symbol { row, col, c });
                 }
             }
-            if !accum.is_empty() {
-                let val: u32 = accum.parse().unwrap();
-                // println!("Found {}", val);
-                numbers.push(Number {
-                    row,
-                    col_start,
-                    col_end,
-                    val,
-                });
+        }
+        let mut number_symbols = vec![Vec::new(); numbers.len()];
+        let mut symbol_numbers = vec![Vec::new(); symbols.len()];
+        for (n, number) in numbers.iter().enumerate() {
+            let rows = number.row.saturating_sub(1)..=number.row + 1;
+            let cols = number.col_start.saturating_sub(1)..=number.col_end + 1;
+            let mut adjacent = rows
+                .flat_map(|r| cols.clone().map(move |c| (r, c)))
+                .filter_map(|pos| index.get(&pos).copied())
+                .collect::<Vec<_>>();
+            adjacent.sort();
+            for s in &adjacent {
+                symbol_numbers[*s].push(n);
             }
+            number_symbols[n] = adjacent;
         }
-        Schematic {
-            raw: lines.iter().map(|l| l.chars().collect()).collect(),
+        Ok(Schematic {
             numbers,
+            symbols,
+            number_symbols,
+            symbol_numbers,
+        })
+    }
+
+    // Numbers next to at least one symbol.
+    fn part_numbers(&self) -> impl Iterator<Item = &Number> {
+        (0..self.numbers.len())
+            .filter(|n| self.adjacent_symbols(*n).next().is_some())
+            .map(|n| &self.numbers[n])
+    }
+
+    fn adjacent_symbols(&self, number: usize) -> impl Iterator<Item = &Symbol> {
+        self.number_symbols[number]
+            .iter()
+            .map(|s| &self.symbols[*s])
+    }
+
+    fn adjacent_numbers(&self, symbol: usize) -> impl Iterator<Item = &Number> {
+        self.symbol_numbers[symbol]
+            .iter()
+            .map(|n| &self.numbers[*n])
+    }
+
+    // Symbols `c` next to exactly `parts` numbers, with those numbers. A number
+    // next to several such symbols is part of each of them.
+    fn gears(&self, c: char, parts: usize) -> Vec<(&Symbol, Vec<&Number>)> {
+        (0..self.symbols.len())
+            .filter(|s| self.symbols[*s].c == c && self.symbol_numbers[*s].len() == parts)
+            .map(|s| (&self.symbols[s], self.adjacent_numbers(s).collect()))
+            .collect()
+    }
+
+    // The sum over gears of the product of their part numbers.
+    fn gear_ratios(&self, c: char, parts: usize) -> Result<u64> {
+        let overflow = || anyhow!("Sum of gear ratios overflows");
+        let mut sum = 0_u64;
+        for (_, numbers) in self.gears(c, parts) {
+            let ratio = numbers
+                .iter()
+                .try_fold(1_u64, |p, n| p.checked_mul(n.val as u64))
+                .ok_or_else(overflow)?;
+            sum = sum.checked_add(ratio).ok_or_else(overflow)?;
         }
+        Ok(sum)
     }
 }
 
-fn main() {
-    let lines = std::io::stdin()
-        .lines()
-        .map(|r| r.unwrap())
-        .collect::<Vec<_>>();
-    let mut sum_of_part_numbers = 0;
-    let schematic = Schematic::from(lines);
-    // println!("{:?}", schematic);
-    for number in &schematic.numbers {
-        if number.is_part_number(&schematic) {
-            // println!("Is part number: {:?}", number);
-            sum_of_part_numbers += number.val;
-        } else {
-            // println!("Is not part number: {:?}", number);
-        }
+fn parse_number(digits: &str, row: usize) -> Result<u32> {
+    digits
+        .parse()
+        .map_err(|e| anyhow!("Bad number {:?} on line {}: {}", digits, row + 1, e))
+}
+
+fn part1(lines: &[String]) -> Result<u32> {
+    Ok(Schematic::from(lines)?.part_numbers().map(|n| n.val).sum())
+}
+
+fn part2(lines: &[String]) -> Result<u64> {
+    Schematic::from(lines)?.gear_ratios('*', 2)
+}
+
+fn main() -> Result<()> {
+    let lines = input_lines();
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|x| x.to_string()).collect()
     }
-    println!("Part 1: {}", sum_of_part_numbers);
-    let mut gears = HashMap::new();
-    for num in &schematic.numbers {
-        if let Some((r, c)) = num.get_gear(&schematic) {
-            gears.entry((r, c)).or_insert(Vec::new()).push(num.val);
-        }
+
+    fn example() -> Vec<String> {
+        lines(indoc! {"
+            467..114..
+            ...*......
+            ..35..633.
+            ......#...
+            617*......
+            .....+.58.
+            ..592.....
+            ......755.
+            ...$.*....
+            .664.598..
+        "})
     }
-    let mut sum_of_gear_ratios = 0;
-    for ((_r, _c), part_nums) in gears {
-        if part_nums.len() == 2 {
-            sum_of_gear_ratios += part_nums[0] * part_nums[1];
-        }
+
+    #[test]
+    fn test_adjacency() -> Result<()> {
+        let s = Schematic::from(&example())?;
+        assert_eq!(s.symbols.len(), 6);
+        assert_eq!(s.numbers.len(), 10);
+        let vals = |n: Vec<&Number>| n.iter().map(|n| n.val).collect::<Vec<_>>();
+        assert_eq!(vals(s.adjacent_numbers(0).collect()), [467, 35]);
+        assert_eq!(vals(s.adjacent_numbers(2).collect()), [617]);
+        // 114 and 58 aren't next to anything.
+        assert_eq!(s.adjacent_symbols(1).count(), 0);
+        assert_eq!(s.adjacent_symbols(5).count(), 0);
+        assert_eq!(
+            s.adjacent_symbols(3).collect::<Vec<_>>(),
+            [&Symbol {
+                row: 3,
+                col: 6,
+                c: '#'
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_gears() -> Result<()> {
+        // 5 touches both '*', which have three parts each.
+        let s = Schematic::from(&lines(indoc! {"
+            2*3.7
+            .5..#
+            4*.1.
+            ..2..
+        "}))?;
+        assert_eq!(s.part_numbers().count(), 7);
+        let gears = s.gears('*', 2);
+        assert_eq!(gears.len(), 0);
+        let gears = s.gears('*', 3);
+        assert_eq!(gears.len(), 2);
+        assert_eq!(s.gear_ratios('*', 3)?, 2 * 3 * 5 + 5 * 4 * 2);
+        assert_eq!(s.gear_ratios('#', 2)?, 7);
+        assert_eq!(s.gear_ratios('#', 3)?, 0);
+
+        // Three ten-digit part numbers multiply past a u64.
+        let s = Schematic::from(&lines(indoc! {"
+            4000000000.4000000000
+            ..........*..........
+            ......4000000000.....
+        "}))?;
+        assert_eq!(s.gears('*', 3).len(), 1);
+        assert!(s.gear_ratios('*', 3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode() -> Result<()> {
+        // Multi-byte symbols mustn't shift later columns, and numbers end at
+        // the end of the line.
+        let s = Schematic::from(&lines(indoc! {"
+            ..€....12
+            .5..ü.3..
+            42..ñ...→
+            ....7..99
+        "}))?;
+        let vals = |n: Vec<&Number>| n.iter().map(|n| n.val).collect::<Vec<_>>();
+        assert_eq!(
+            s.symbols
+                .iter()
+                .map(|s| (s.row, s.col, s.c))
+                .collect::<Vec<_>>(),
+            [(0, 2, '€'), (1, 4, 'ü'), (2, 4, 'ñ'), (2, 8, '→')]
+        );
+        assert_eq!(
+            s.numbers[0],
+            Number {
+                row: 0,
+                col_start: 7,
+                col_end: 8,
+                val: 12
+            }
+        );
+        assert_eq!(vals(s.adjacent_numbers(0).collect()), [5]);
+        assert_eq!(vals(s.adjacent_numbers(1).collect()), []);
+        assert_eq!(vals(s.adjacent_numbers(2).collect()), [7]);
+        assert_eq!(vals(s.adjacent_numbers(3).collect()), [99]);
+        assert_eq!(vals(s.part_numbers().collect()), [5, 7, 99]);
+        assert_eq!(s.gear_ratios('ñ', 1)?, 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&example())?, 4361);
+        Ok(())
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&example())?, 467835);
+        Ok(())
     }
-    println!("Part 1: {}", sum_of_gear_ratios);
 }
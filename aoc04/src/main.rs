@@ -1,51 +1,246 @@
 use std::collections::HashSet;
 
+use anyhow::{anyhow, Result};
+use util::input_lines;
+
+#[derive(Debug, Clone)]
 struct Game {
+    id: u32,
     winning: HashSet<u32>,
     yours: Vec<u32>,
 }
 
 impl Game {
-    fn parse(g: &str) -> Game {
-        let (_game, rest) = g.split_once(": ").unwrap();
-        let (winning, yours) = rest.split_once(" | ").unwrap();
-        Game {
-            winning: winning
-                .split_whitespace()
-                .map(|x| x.parse::<u32>().unwrap())
-                .collect(),
-            yours: yours
-                .split_whitespace()
-                .map(|x| x.parse::<u32>().unwrap())
-                .collect(),
+    fn parse(g: &str) -> Result<Game> {
+        let (game, rest) = g
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Expected \"Card id: winning | yours\", got {:?}", g))?;
+        let id = game
+            .strip_prefix("Card")
+            .and_then(|id| id.trim().parse().ok())
+            .ok_or_else(|| anyhow!("Bad card id {:?}", game))?;
+        let (winning, yours) = rest
+            .split_once('|')
+            .ok_or_else(|| anyhow!("Card {}: missing \"|\"", id))?;
+        let numbers = |s: &str| {
+            s.split_whitespace()
+                .map(|x| {
+                    x.parse::<u32>()
+                        .map_err(|e| anyhow!("Card {}: bad number {:?}: {}", id, x, e))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Game {
+            id,
+            winning: numbers(winning)?.into_iter().collect(),
+            yours: numbers(yours)?,
+        })
+    }
+
+    fn matches(&self) -> usize {
+        self.yours
+            .iter()
+            .filter(|y| self.winning.contains(y))
+            .count()
+    }
+
+    fn points(&self) -> Result<u128> {
+        match self.matches() {
+            0 => Ok(0),
+            m => 1_u128
+                .checked_shl(m as u32 - 1)
+                .ok_or_else(|| anyhow!("Card {}: {} matches is too many points", self.id, m)),
         }
     }
 }
 
-fn main() {
-    let lines = std::io::stdin()
-        .lines()
-        .map(|r| r.unwrap())
-        .collect::<Vec<_>>();
-    let mut score = 0;
-    for line in &lines {
-        let g = Game::parse(&line);
-        let num_matches = g.yours.iter().filter(|y| g.winning.contains(y)).count() as u32;
-        if num_matches > 0 {
-            score += 2_u32.pow(num_matches - 1);
+// What to do when a card wins copies of cards past the end of the table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overflow {
+    // Only copy the cards that exist.
+    Clamp,
+    Error,
+}
+
+#[derive(Debug)]
+struct Scratchcards {
+    cards: Vec<Game>,
+}
+
+impl Scratchcards {
+    fn parse(lines: &[String]) -> Result<Scratchcards> {
+        Ok(Scratchcards {
+            cards: lines
+                .iter()
+                .map(|l| Game::parse(l))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    fn matches(&self) -> Vec<usize> {
+        self.cards.iter().map(|g| g.matches()).collect()
+    }
+
+    fn points(&self) -> Result<u128> {
+        let mut sum = 0_u128;
+        for card in &self.cards {
+            sum = sum
+                .checked_add(card.points()?)
+                .ok_or_else(|| anyhow!("Total points overflow"))?;
+        }
+        Ok(sum)
+    }
+
+    // How many of each card you end up with, counting the original.
+    fn copies(&self, overflow: Overflow) -> Result<Vec<u128>> {
+        let mut copies = vec![1_u128; self.cards.len()];
+        for (idx, num_matches) in self.matches().into_iter().enumerate() {
+            let last = idx + num_matches;
+            if last >= self.cards.len() && overflow == Overflow::Error {
+                return Err(anyhow!(
+                    "Card {} wins copies of {} cards, but only {} follow it",
+                    self.cards[idx].id,
+                    num_matches,
+                    self.cards.len() - idx - 1
+                ));
+            }
+            for m in idx + 1..=std::cmp::min(last, self.cards.len() - 1) {
+                copies[m] = copies[m]
+                    .checked_add(copies[idx])
+                    .ok_or_else(|| anyhow!("Copies of card {} overflow", self.cards[m].id))?;
+            }
         }
+        Ok(copies)
     }
-    println!("Part 1: {}", score);
-    let mut copies = Vec::new();
-    for _line in &lines {
-        copies.push(1_u32);
+
+    fn total_copies(&self, overflow: Overflow) -> Result<u128> {
+        self.copies(overflow)?
+            .iter()
+            .try_fold(0_u128, |sum, c| sum.checked_add(*c))
+            .ok_or_else(|| anyhow!("Total number of cards overflows"))
     }
-    for (idx, line) in lines.iter().enumerate() {
-        let g = Game::parse(&line);
-        let num_matches = g.yours.iter().filter(|y| g.winning.contains(y)).count() as usize;
-        for m in 1..=num_matches {
-            copies[idx + m] += copies[idx];
+}
+
+fn part1(lines: &[String]) -> Result<u128> {
+    Scratchcards::parse(lines)?.points()
+}
+
+fn part2(lines: &[String], overflow: Overflow) -> Result<u128> {
+    Scratchcards::parse(lines)?.total_copies(overflow)
+}
+
+fn main() -> Result<()> {
+    let lines = input_lines();
+    let flag = |f: &str| std::env::args().any(|a| a == f);
+    let overflow = if flag("--strict") {
+        Overflow::Error
+    } else {
+        Overflow::Clamp
+    };
+    if flag("--verbose") {
+        let cards = Scratchcards::parse(&lines)?;
+        for ((card, matches), copies) in cards
+            .cards
+            .iter()
+            .zip(cards.matches())
+            .zip(cards.copies(overflow)?)
+        {
+            println!("Card {}: {} matches, {} copies", card.id, matches, copies);
         }
     }
-    println!("Part 2: {}", copies.iter().sum::<u32>());
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines, overflow)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|x| x.to_string()).collect()
+    }
+
+    fn example() -> Vec<String> {
+        lines(indoc! {"
+            Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+            Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+            Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+            Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+            Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+            Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+        "})
+    }
+
+    #[test]
+    fn test_parse() {
+        let card = Game::parse("Card  12: 1 2 | 2 3 2").unwrap();
+        assert_eq!(card.id, 12);
+        assert_eq!(card.matches(), 2);
+        let err = |s| Game::parse(s).unwrap_err().to_string();
+        assert_eq!(err("Card x: 1 | 2"), "Bad card id \"Card x\"");
+        assert_eq!(err("Card 3: 1 2"), "Card 3: missing \"|\"");
+        assert_eq!(
+            err("Card 4: 1 | 2 z"),
+            "Card 4: bad number \"z\": invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn test_copies() -> Result<()> {
+        let cards = Scratchcards::parse(&example())?;
+        assert_eq!(cards.matches(), [4, 2, 2, 1, 0, 0]);
+        assert_eq!(cards.copies(Overflow::Error)?, [1, 2, 4, 8, 14, 1]);
+
+        // The last two cards win more cards than there are.
+        let cards = Scratchcards::parse(&lines(indoc! {"
+            Card 1: 1 2 | 1 2
+            Card 2: 1 2 | 1 2
+            Card 3: 1 | 1
+        "}))?;
+        assert_eq!(cards.copies(Overflow::Clamp)?, [1, 2, 4]);
+        assert_eq!(
+            cards.copies(Overflow::Error).unwrap_err().to_string(),
+            "Card 2 wins copies of 2 cards, but only 1 follow it"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_large() -> Result<()> {
+        // Every card wins a copy of the next two, so card n ends up with
+        // F(n + 3) - 1 copies, past what fits in a u64.
+        let card = "Card 1: 1 2 | 1 2".to_string();
+        let cards = Scratchcards::parse(&vec![card.clone(); 120])?;
+        let copies = cards.copies(Overflow::Clamp)?;
+        assert_eq!(copies[99], 927372692193078999176 - 1);
+        assert!(cards.total_copies(Overflow::Clamp)? > u64::MAX as u128);
+        let cards = Scratchcards::parse(&vec![card; 200])?;
+        assert!(cards.copies(Overflow::Clamp).is_err());
+
+        // Each card is worth 2^127 points, and two of them don't fit.
+        let numbers = (1..=128)
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let card = format!("Card 1: {0} | {0}", numbers);
+        let cards = vec![card; 2];
+        assert_eq!(part1(&cards[..1])?, 1 << 127);
+        assert!(part1(&cards).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&example())?, 13);
+        Ok(())
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&example(), Overflow::Error)?, 30);
+        Ok(())
+    }
 }
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
 #[derive(Debug, Clone)]
@@ -8,47 +11,152 @@ struct Map {
 }
 
 impl Map {
-    fn parse(lines: &[String]) -> Vec<Map> {
-        let mut v = Vec::new();
-        for line in lines {
-            if line.is_empty() {
-                break;
-            }
-            let mut iter = line.split_whitespace();
-            v.push(Map {
-                dest: iter.next().unwrap().parse().unwrap(),
-                src: iter.next().unwrap().parse().unwrap(),
-                len: iter.next().unwrap().parse().unwrap(),
-            })
+    fn parse(line: &str) -> Result<Map> {
+        let v = line
+            .split_whitespace()
+            .map(|x| x.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("Bad number in {:?}: {}", line, e))?;
+        match v[..] {
+            [dest, src, len] => Ok(Map { dest, src, len }),
+            _ => Err(anyhow!("Expected \"dest src len\", got {:?}", line)),
         }
-        v.sort_by(|a, b| a.src.cmp(&b.src));
-        v
     }
     fn get_dest(&self, src: u64) -> u64 {
         assert!(self.contains(src));
         self.dest + src - self.src
     }
     fn contains(&self, src: u64) -> bool {
-        src >= self.src && src < self.src + self.len
+        src >= self.src && src - self.src < self.len
     }
     fn src_upper_limit(&self) -> u64 {
-        let max = self.src + self.len - 1;
+        let max = self.src + (self.len - 1);
         assert!(self.contains(max));
-        assert!(!self.contains(max + 1));
+        assert!(max.checked_add(1).is_none_or(|m| !self.contains(m)));
         max
     }
 }
 
+// The maps from one category to another, from an "X-to-Y map:" section.
+#[derive(Debug, Clone)]
+struct Stage {
+    from: String,
+    to: String,
+    // Line number of the header.
+    line: usize,
+    maps: Vec<Map>,
+    // Line number of each map.
+    map_lines: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    ZeroLength { line: usize },
+    // A source or destination range that runs past u64::MAX.
+    Overflow { line: usize, what: &'static str },
+    // Two maps, by line, covering some of the same sources.
+    SourceOverlap { lines: (usize, usize) },
+    // Two maps sending some values to the same place.
+    DestOverlap { lines: (usize, usize) },
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::ZeroLength { line } => write!(f, "Line {}: range is empty", line),
+            Problem::Overflow { line, what } => {
+                write!(f, "Line {}: {} range runs past {}", line, what, u64::MAX)
+            }
+            Problem::SourceOverlap { lines } => write!(
+                f,
+                "Lines {} and {}: source ranges overlap",
+                lines.0, lines.1
+            ),
+            Problem::DestOverlap { lines } => write!(
+                f,
+                "Lines {} and {}: destination ranges overlap",
+                lines.0, lines.1
+            ),
+        }
+    }
+}
+
+impl Problem {
+    // The lines involved, first line first, for sorting.
+    fn lines(&self) -> (usize, usize) {
+        match self {
+            Problem::ZeroLength { line } | Problem::Overflow { line, .. } => (*line, *line),
+            Problem::SourceOverlap { lines } | Problem::DestOverlap { lines } => *lines,
+        }
+    }
+
+    // Overlapping destinations only matter for the inverse, since several
+    // sources can go to the same place.
+    fn is_warning(&self) -> bool {
+        matches!(self, Problem::DestOverlap { .. })
+    }
+}
+
+impl Stage {
+    // Problems with the maps that would make the answers depend on the order
+    // of the maps, overflow, or leave the mapping without an inverse.
+    fn validate(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        // The last source and destination covered by each usable map.
+        let mut ranges = Vec::new();
+        for (m, line) in self.maps.iter().zip(&self.map_lines) {
+            let line = *line;
+            if m.len == 0 {
+                problems.push(Problem::ZeroLength { line });
+                continue;
+            }
+            let src_last = m.src.checked_add(m.len - 1);
+            let dest_last = m.dest.checked_add(m.len - 1);
+            for (last, what) in [(src_last, "source"), (dest_last, "destination")] {
+                if last.is_none() {
+                    problems.push(Problem::Overflow { line, what });
+                }
+            }
+            if let (Some(src_last), Some(dest_last)) = (src_last, dest_last) {
+                ranges.push((line, (m.src, src_last), (m.dest, dest_last)));
+            }
+        }
+        for (line_a, line_b) in overlaps(ranges.iter().map(|(l, src, _)| (*l, *src))) {
+            problems.push(Problem::SourceOverlap {
+                lines: (line_a, line_b),
+            });
+        }
+        for (line_a, line_b) in overlaps(ranges.iter().map(|(l, _, dest)| (*l, *dest))) {
+            problems.push(Problem::DestOverlap {
+                lines: (line_a, line_b),
+            });
+        }
+        problems
+    }
+}
+
+// Every pair of lines whose inclusive ranges overlap, sorted. Going through
+// the ranges by start, each one overlaps the earlier ones still running.
+fn overlaps(ranges: impl Iterator<Item = (usize, (u64, u64))>) -> Vec<(usize, usize)> {
+    let mut ranges = ranges.collect::<Vec<_>>();
+    ranges.sort_by_key(|(_, r)| *r);
+    let mut found = Vec::new();
+    let mut running: Vec<(usize, u64)> = Vec::new();
+    for (line, (first, last)) in ranges {
+        running.retain(|(_, end)| *end >= first);
+        for (l, _) in &running {
+            found.push((std::cmp::min(*l, line), std::cmp::max(*l, line)));
+        }
+        running.push((line, last));
+    }
+    found.sort();
+    found
+}
+
 #[derive(Debug, Default)]
 struct Input {
     seeds: Vec<u64>,
-    seed_to_soil: Vec<Map>,
-    soil_to_fertilizer: Vec<Map>,
-    fertilizer_to_water: Vec<Map>,
-    water_to_light: Vec<Map>,
-    light_to_temp: Vec<Map>,
-    temp_to_humidity: Vec<Map>,
-    humidity_to_location: Vec<Map>,
+    stages: Vec<Stage>,
 }
 
 trait GetDest {
@@ -57,23 +165,19 @@ trait GetDest {
     fn get_src_ranges(&self, min: u64, max: u64) -> Vec<(u64, u64)> {
         let mut v = Vec::new();
         let mut x = min;
-        while x < max {
+        while x <= max {
             v.push(self.get_first_src_range(x, max));
-            x = v.last().unwrap().1 + 1;
+            match v.last().unwrap().1.checked_add(1) {
+                Some(next) => x = next,
+                None => break,
+            }
         }
         v
     }
     fn get_dest_ranges(&self, src_min: u64, src_max: u64) -> Vec<(u64, u64)>;
-    fn get_many_dest_ranges(&self, ranges: &[(u64, u64)]) -> Vec<(u64, u64)> {
-        let mut v = Vec::new();
-        for (min, max) in ranges {
-            v.extend(self.get_dest_ranges(*min, *max));
-        }
-        v
-    }
 }
 
-impl GetDest for Vec<Map> {
+impl GetDest for [Map] {
     fn get_dest(&self, src: u64) -> u64 {
         for m in self {
             if m.contains(src) {
@@ -106,93 +210,390 @@ impl GetDest for Vec<Map> {
     }
 }
 
+// Sources `src..=last` go to `dest..`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Piece {
+    src: u64,
+    last: u64,
+    dest: u64,
+}
+
+impl Piece {
+    fn dest_last(&self) -> u64 {
+        self.dest + (self.last - self.src)
+    }
+}
+
+// A mapping of every u64, as pieces sorted by source, covering 0..=u64::MAX
+// with no gaps or overlaps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mapping {
+    pieces: Vec<Piece>,
+}
+
+impl Mapping {
+    fn identity() -> Mapping {
+        Mapping {
+            pieces: vec![Piece {
+                src: 0,
+                last: u64::MAX,
+                dest: 0,
+            }],
+        }
+    }
+
+    // The same mapping as `get_dest` on the maps, which must be sorted by
+    // source.
+    fn from_maps(maps: &[Map]) -> Mapping {
+        Mapping::merged(
+            maps.get_src_ranges(0, u64::MAX)
+                .into_iter()
+                .zip(maps.get_dest_ranges(0, u64::MAX))
+                .map(|((min, max), (dest, _))| Piece {
+                    src: min,
+                    last: max,
+                    dest,
+                })
+                .collect(),
+        )
+    }
+
+    // Joins neighboring pieces that continue the same offset.
+    fn merged(pieces: Vec<Piece>) -> Mapping {
+        let mut merged: Vec<Piece> = Vec::new();
+        for p in pieces {
+            match merged.last_mut() {
+                Some(q) if q.last + 1 == p.src && q.dest_last().checked_add(1) == Some(p.dest) => {
+                    q.last = p.last;
+                }
+                _ => merged.push(p),
+            }
+        }
+        Mapping { pieces: merged }
+    }
+
+    fn get(&self, src: u64) -> u64 {
+        let p = &self.pieces[self.pieces.partition_point(|p| p.last < src)];
+        p.dest + (src - p.src)
+    }
+
+    // The pieces covering `min..=max`, cut down to fit.
+    fn restrict(&self, min: u64, max: u64) -> impl Iterator<Item = Piece> + '_ {
+        let first = self.pieces.partition_point(|p| p.last < min);
+        self.pieces[first..]
+            .iter()
+            .take_while(move |p| p.src <= max)
+            .map(move |p| {
+                let src = std::cmp::max(p.src, min);
+                Piece {
+                    src,
+                    last: std::cmp::min(p.last, max),
+                    dest: p.dest + (src - p.src),
+                }
+            })
+    }
+
+    // This mapping followed by `next`.
+    fn then(&self, next: &Mapping) -> Mapping {
+        let mut pieces = Vec::new();
+        for p in &self.pieces {
+            for q in next.restrict(p.dest, p.dest_last()) {
+                pieces.push(Piece {
+                    src: p.src + (q.src - p.dest),
+                    last: p.src + (q.last - p.dest),
+                    dest: q.dest,
+                });
+            }
+        }
+        Mapping::merged(pieces)
+    }
+
+    // The smallest value that anything in `min..=max` maps to.
+    fn min_dest(&self, min: u64, max: u64) -> u64 {
+        self.restrict(min, max).map(|p| p.dest).min().unwrap()
+    }
+
+    // The sources that map into `min..=max`, as sorted, disjoint ranges.
+    fn preimage(&self, min: u64, max: u64) -> Vec<(u64, u64)> {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for p in &self.pieces {
+            let (lo, hi) = (
+                std::cmp::max(p.dest, min),
+                std::cmp::min(p.dest_last(), max),
+            );
+            if lo > hi {
+                continue;
+            }
+            let range = (p.src + (lo - p.dest), p.src + (hi - p.dest));
+            match ranges.last_mut() {
+                Some(r) if r.1 + 1 == range.0 => r.1 = range.1,
+                _ => ranges.push(range),
+            }
+        }
+        ranges
+    }
+
+    // The inverse mapping, which only exists if no two values map to the
+    // same place.
+    fn inverse(&self) -> Result<Mapping> {
+        let mut pieces = self
+            .pieces
+            .iter()
+            .map(|p| Piece {
+                src: p.dest,
+                last: p.dest_last(),
+                dest: p.src,
+            })
+            .collect::<Vec<_>>();
+        pieces.sort_by_key(|p| p.src);
+        for w in pieces.windows(2) {
+            if w[0].last >= w[1].src {
+                return Err(anyhow!(
+                    "{}..={} are reached from more than one place",
+                    w[1].src,
+                    std::cmp::min(w[0].last, w[1].last)
+                ));
+            }
+        }
+        Ok(Mapping::merged(pieces))
+    }
+}
+
 impl Input {
-    fn parse(lines: &[String]) -> Input {
-        let seeds = lines[0]
+    fn parse(lines: &[String]) -> Result<Input> {
+        let seeds = lines
+            .first()
+            .and_then(|l| l.strip_prefix("seeds:"))
+            .ok_or_else(|| anyhow!("Expected \"seeds: ...\" on line 1"))?
             .split_whitespace()
-            .skip(1)
-            .map(|x| x.parse().unwrap())
+            .map(|x| x.parse())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("Bad seed on line 1: {}", e))?;
+        let mut stages: Vec<Stage> = Vec::new();
+        for (n, line) in lines.iter().enumerate().skip(1) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_suffix(" map:") {
+                let (from, to) = header
+                    .split_once("-to-")
+                    .ok_or_else(|| anyhow!("Bad map header on line {}: {:?}", n + 1, line))?;
+                if let Some(dup) = stages.iter().find(|s| s.from == from) {
+                    return Err(anyhow!(
+                        "Duplicate maps from {:?} on lines {} and {}",
+                        from,
+                        dup.line,
+                        n + 1
+                    ));
+                }
+                stages.push(Stage {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    line: n + 1,
+                    maps: Vec::new(),
+                    map_lines: Vec::new(),
+                });
+                continue;
+            }
+            let stage = stages
+                .last_mut()
+                .ok_or_else(|| anyhow!("Map on line {} comes before any header", n + 1))?;
+            stage
+                .maps
+                .push(Map::parse(line).map_err(|e| anyhow!("Line {}: {}", n + 1, e))?);
+            stage.map_lines.push(n + 1);
+        }
+        for stage in &mut stages {
+            let mut order = (0..stage.maps.len()).collect::<Vec<_>>();
+            order.sort_by_key(|i| stage.maps[*i].src);
+            stage.maps = order.iter().map(|i| stage.maps[*i].clone()).collect();
+            stage.map_lines = order.iter().map(|i| stage.map_lines[*i]).collect();
+        }
+        Ok(Input { seeds, stages })
+    }
+    fn validate(&self) -> Vec<Problem> {
+        let mut problems = self
+            .stages
+            .iter()
+            .flat_map(|s| s.validate())
             .collect::<Vec<_>>();
-        let mut start: usize = 3;
-        let seed_to_soil = Map::parse(&lines[start..]);
-        start += seed_to_soil.len() + 2;
-        let soil_to_fertilizer = Map::parse(&lines[start..]);
-        start += soil_to_fertilizer.len() + 2;
-        let fertilizer_to_water = Map::parse(&lines[start..]);
-        start += fertilizer_to_water.len() + 2;
-        let water_to_light = Map::parse(&lines[start..]);
-        start += water_to_light.len() + 2;
-        let light_to_temp = Map::parse(&lines[start..]);
-        start += light_to_temp.len() + 2;
-        let temp_to_humidity = Map::parse(&lines[start..]);
-        start += temp_to_humidity.len() + 2;
-        let humidity_to_location = Map::parse(&lines[start..]);
-        Input {
-            seeds,
-            seed_to_soil,
-            soil_to_fertilizer,
-            fertilizer_to_water,
-            water_to_light,
-            light_to_temp,
-            temp_to_humidity,
-            humidity_to_location,
-        }
-    }
-    fn get_loc(&self, src: u64) -> u64 {
-        self.humidity_to_location.get_dest(
-            self.temp_to_humidity.get_dest(
-                self.light_to_temp.get_dest(
-                    self.water_to_light.get_dest(
-                        self.fertilizer_to_water.get_dest(
-                            self.soil_to_fertilizer
-                                .get_dest(self.seed_to_soil.get_dest(src)),
-                        ),
-                    ),
-                ),
-            ),
-        )
+        problems.sort_by_key(|p| p.lines());
+        problems
     }
-    fn get_best(&self, min: u64, max: u64) -> u64 {
-        let locs = self.humidity_to_location.get_many_dest_ranges(
-            &self.temp_to_humidity.get_many_dest_ranges(
-                &self.light_to_temp.get_many_dest_ranges(
-                    &self.water_to_light.get_many_dest_ranges(
-                        &self.fertilizer_to_water.get_many_dest_ranges(
-                            &self
-                                .soil_to_fertilizer
-                                .get_many_dest_ranges(&self.seed_to_soil.get_dest_ranges(min, max)),
-                        ),
-                    ),
-                ),
-            ),
-        );
-        let mut min = locs[0].0;
-        for loc in locs {
-            min = std::cmp::min(min, loc.0);
+    // The stages leading from one category to another, following the names.
+    fn path(&self, from: &str, to: &str) -> Result<Vec<&Stage>> {
+        let by_from = self
+            .stages
+            .iter()
+            .map(|s| (s.from.as_str(), s))
+            .collect::<HashMap<_, _>>();
+        let mut path = Vec::new();
+        let mut category = from;
+        while category != to {
+            let stage = by_from
+                .get(category)
+                .ok_or_else(|| anyhow!("No map from {:?} on the way to {:?}", category, to))?;
+            if path.len() == self.stages.len() {
+                return Err(anyhow!("Maps from {:?} go round in a loop", from));
+            }
+            path.push(*stage);
+            category = &stage.to;
+        }
+        Ok(path)
+    }
+    // All the stages from one category to another as a single mapping.
+    fn compose(&self, from: &str, to: &str) -> Result<Mapping> {
+        Ok(self
+            .path(from, to)?
+            .iter()
+            .fold(Mapping::identity(), |m, stage| {
+                m.then(&Mapping::from_maps(&stage.maps))
+            }))
+    }
+}
+
+fn part1(lines: &[String]) -> Result<u64> {
+    let i = Input::parse(lines)?;
+    let m = i.compose("seed", "location")?;
+    i.seeds
+        .iter()
+        .map(|s| m.get(*s))
+        .min()
+        .ok_or_else(|| anyhow!("No seeds"))
+}
+
+fn part2(lines: &[String]) -> Result<u64> {
+    let i = Input::parse(lines)?;
+    let m = i.compose("seed", "location")?;
+    let mut min_loc = None;
+    for pair in i.seeds.chunks(2) {
+        let [start, len] = pair else {
+            return Err(anyhow!("Seed ranges must come in pairs"));
+        };
+        if *len == 0 {
+            continue;
         }
-        min
+        let last = start
+            .checked_add(len - 1)
+            .ok_or_else(|| anyhow!("Seed range {} {} overflows", start, len))?;
+        let loc = m.min_dest(*start, last);
+        min_loc = Some(min_loc.map_or(loc, |m| std::cmp::min(m, loc)));
     }
+    min_loc.ok_or_else(|| anyhow!("No seeds"))
 }
 
-fn main() {
-    let i = Input::parse(&input_lines());
-    let mut min_loc = i.get_loc(i.seeds[0]);
-    for seed in &i.seeds {
-        min_loc = std::cmp::min(min_loc, i.get_loc(*seed));
+fn main() -> Result<()> {
+    let lines = input_lines();
+    let (warnings, problems) = Input::parse(&lines)?
+        .validate()
+        .into_iter()
+        .partition::<Vec<_>, _>(|p| p.is_warning());
+    for warning in &warnings {
+        eprintln!("Warning: {}", warning);
     }
-    println!("Part 1: {}", min_loc);
-    let mut min_loc = i.get_loc(i.seeds[0]);
-    let mut iter = i.seeds.iter();
-    while let Some(start) = iter.next() {
-        min_loc = std::cmp::min(min_loc, i.get_best(*start, *start + *iter.next().unwrap()));
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        return Err(anyhow!("{} problems in the almanac", problems.len()));
     }
-    println!("Part 2: {}", min_loc);
+    let args = std::env::args().collect::<Vec<_>>();
+    let flag = |f: &str| args.iter().any(|a| a == f);
+    // The whole seed to location mapping, or its inverse.
+    if flag("--table") || flag("--inverse") {
+        let mut m = Input::parse(&lines)?.compose("seed", "location")?;
+        let (from, to) = if flag("--inverse") {
+            m = m.inverse()?;
+            ("locations", "seeds")
+        } else {
+            ("seeds", "locations")
+        };
+        for p in m.pieces {
+            println!(
+                "{} {}..={} -> {} {}..={}",
+                from,
+                p.src,
+                p.last,
+                to,
+                p.dest,
+                p.dest_last()
+            );
+        }
+    }
+    // Which seeds end up at a location, given like --location 46.
+    if let Some(loc) = args
+        .iter()
+        .position(|a| a == "--location")
+        .map(|i| args.get(i + 1))
+    {
+        let loc = loc
+            .ok_or_else(|| anyhow!("--location needs a location"))?
+            .parse()?;
+        let m = Input::parse(&lines)?.compose("seed", "location")?;
+        for (min, max) in m.preimage(loc, loc) {
+            println!("Seeds {}..={} end up at location {}", min, max, loc);
+        }
+    }
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use indoc::indoc;
+
     use super::*;
 
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|x| x.to_string()).collect()
+    }
+
+    // The location for a seed, one stage at a time.
+    fn get_loc(i: &Input, src: u64) -> Result<u64> {
+        Ok(i.path("seed", "location")?
+            .iter()
+            .fold(src, |x, stage| stage.maps.get_dest(x)))
+    }
+
+    fn example() -> Vec<String> {
+        lines(indoc! {"
+            seeds: 79 14 55 13
+
+            seed-to-soil map:
+            50 98 2
+            52 50 48
+
+            soil-to-fertilizer map:
+            0 15 37
+            37 52 2
+            39 0 15
+
+            fertilizer-to-water map:
+            49 53 8
+            0 11 42
+            42 0 7
+            57 7 4
+
+            water-to-light map:
+            88 18 7
+            18 25 70
+
+            light-to-temperature map:
+            45 77 23
+            81 45 19
+            68 64 13
+
+            temperature-to-humidity map:
+            0 69 1
+            1 0 69
+
+            humidity-to-location map:
+            60 56 37
+            56 93 4
+        "})
+    }
+
     #[test]
     fn test_map() {
         let map = Map {
@@ -211,7 +612,7 @@ mod tests {
 
     #[test]
     fn test_get_dest() {
-        let v = vec![Map {
+        let v = [Map {
             dest: 10,
             src: 100,
             len: 10,
@@ -245,4 +646,230 @@ mod tests {
             [(10, 99), (10, 19), (110, 1000)]
         );
     }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let i = Input::parse(&example())?;
+        assert_eq!(i.seeds, [79, 14, 55, 13]);
+        assert_eq!(i.stages.len(), 7);
+        let path = i.path("soil", "water")?;
+        assert_eq!(
+            path.iter().map(|s| s.to.as_str()).collect::<Vec<_>>(),
+            ["fertilizer", "water"]
+        );
+        assert_eq!(path[0].line, 7);
+        assert!(i.path("seed", "seed")?.is_empty());
+        assert_eq!(
+            i.path("soil", "seed").unwrap_err().to_string(),
+            "No map from \"location\" on the way to \"seed\""
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reordered() -> Result<()> {
+        // Sections in any order, with extra blank lines, give the same answer.
+        let example = example();
+        let mut sections = example[2..]
+            .split(|l| l.is_empty())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        sections.reverse();
+        let mut shuffled = vec![example[0].clone(), String::new()];
+        for section in sections {
+            shuffled.extend(section.iter().cloned());
+            shuffled.extend([String::new(), String::new()]);
+        }
+        assert_eq!(part1(&shuffled)?, 35);
+        assert_eq!(part2(&shuffled)?, 46);
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> Result<()> {
+        let err = |s: &str| Input::parse(&lines(s)).unwrap_err().to_string();
+        assert_eq!(
+            err("seeds: 1\n\na-to-b map:\n1 2 3\n\na-to-c map:\n"),
+            "Duplicate maps from \"a\" on lines 3 and 6"
+        );
+        assert_eq!(
+            err("seeds: 1\n1 2 3"),
+            "Map on line 2 comes before any header"
+        );
+        assert_eq!(
+            err("seeds: 1\na-to-b map:\n1 2"),
+            "Line 3: Expected \"dest src len\", got \"1 2\""
+        );
+        assert_eq!(
+            err("seeds: 1\nab map:"),
+            "Bad map header on line 2: \"ab map:\""
+        );
+        assert!(Input::parse(&lines("1 2 3")).is_err());
+
+        let missing = example()
+            .into_iter()
+            .filter(|l| !l.starts_with("water-to-light"))
+            .collect::<Vec<_>>();
+        assert!(part1(&missing).is_err());
+        let looped = lines("seeds: 1\nseed-to-soil map:\nsoil-to-seed map:");
+        assert_eq!(
+            get_loc(&Input::parse(&looped)?, 1).unwrap_err().to_string(),
+            "Maps from \"seed\" go round in a loop"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compose() -> Result<()> {
+        let i = Input::parse(&example())?;
+        let m = i.compose("seed", "location")?;
+        for seed in 0..200 {
+            assert_eq!(m.get(seed), get_loc(&i, seed)?);
+        }
+        assert_eq!(m.get(u64::MAX), u64::MAX);
+        assert_eq!(m.min_dest(82, 82), 46);
+        assert_eq!(m.min_dest(79, 92), 46);
+        assert_eq!(i.compose("soil", "soil")?, Mapping::identity());
+        // Piece by piece, the stages compose the same way.
+        let soil_to_water = i.compose("soil", "water")?;
+        let seed_to_soil = i.compose("seed", "soil")?;
+        assert_eq!(
+            seed_to_soil.then(&soil_to_water),
+            i.compose("seed", "water")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_maps() {
+        // Overlapping maps behave like `get_dest`, with the first one winning.
+        let maps = vec![
+            Map {
+                dest: 10,
+                src: 100,
+                len: 10,
+            },
+            Map {
+                dest: 500,
+                src: 105,
+                len: 10,
+            },
+            Map {
+                dest: 0,
+                src: u64::MAX - 1,
+                len: 2,
+            },
+        ];
+        assert_eq!(maps[2].src_upper_limit(), u64::MAX);
+        let m = Mapping::from_maps(&maps);
+        for src in (90..130).chain([u64::MAX - 2, u64::MAX - 1, u64::MAX]) {
+            assert_eq!(m.get(src), maps.get_dest(src), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_reverse() -> Result<()> {
+        let i = Input::parse(&example())?;
+        let m = i.compose("seed", "location")?;
+        assert_eq!(m.preimage(46, 46), [(82, 82)]);
+        assert_eq!(m.preimage(35, 35), [(13, 13)]);
+        let inverse = m.inverse()?;
+        for loc in 0..200 {
+            assert_eq!(m.get(inverse.get(loc)), loc);
+            assert_eq!(m.preimage(loc, loc), [(inverse.get(loc), inverse.get(loc))]);
+        }
+        // Everything below 60 maps to itself, and 50..=59 also end up there.
+        let many_to_one = Mapping::from_maps(&[Map {
+            dest: 50,
+            src: 100,
+            len: 10,
+        }]);
+        assert_eq!(many_to_one.preimage(55, 109), [(55, 99), (105, 109)]);
+        assert_eq!(many_to_one.preimage(100, 109), []);
+        assert!(many_to_one.inverse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        assert_eq!(Input::parse(&example())?.validate(), []);
+        let i = Input::parse(&lines(indoc! {"
+            seeds: 1
+
+            a-to-b map:
+            100 10 5
+            0 12 5
+            50 20 0
+            200 30 3
+            102 40 2
+            0 18446744073709551615 2
+            18446744073709551615 50 2
+            300 11 1
+        "}))?;
+        let problems = i.validate();
+        assert_eq!(
+            problems,
+            [
+                Problem::SourceOverlap { lines: (4, 5) },
+                Problem::DestOverlap { lines: (4, 8) },
+                Problem::SourceOverlap { lines: (4, 11) },
+                Problem::ZeroLength { line: 6 },
+                Problem::Overflow {
+                    line: 9,
+                    what: "source"
+                },
+                Problem::Overflow {
+                    line: 10,
+                    what: "destination"
+                },
+            ]
+        );
+        assert_eq!(
+            problems.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+            [
+                "Lines 4 and 5: source ranges overlap",
+                "Lines 4 and 8: destination ranges overlap",
+                "Lines 4 and 11: source ranges overlap",
+                "Line 6: range is empty",
+                "Line 9: source range runs past 18446744073709551615",
+                "Line 10: destination range runs past 18446744073709551615",
+            ]
+        );
+
+        // Two maps sending seeds to the same locations are fine, except for
+        // the inverse.
+        let many_to_one = lines(indoc! {"
+            seeds: 12 22
+
+            seed-to-location map:
+            0 10 5
+            0 20 5
+        "});
+        let problems = Input::parse(&many_to_one)?.validate();
+        assert_eq!(problems, [Problem::DestOverlap { lines: (4, 5) }]);
+        assert!(problems.iter().all(|p| p.is_warning()));
+        assert_eq!(part1(&many_to_one)?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlaps() {
+        // B and C both overlap A, and each other.
+        let ranges = [(1, (0, 100)), (2, (10, 20)), (3, (15, 30))];
+        assert_eq!(overlaps(ranges.into_iter()), [(1, 2), (1, 3), (2, 3)]);
+        let apart = [(1, (0, 9)), (2, (10, 20)), (3, (21, 21))];
+        assert_eq!(overlaps(apart.into_iter()), []);
+    }
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&example())?, 35);
+        Ok(())
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&example())?, 46);
+        Ok(())
+    }
 }
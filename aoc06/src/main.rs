@@ -1,3 +1,4 @@
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
 #[derive(Debug)]
@@ -7,67 +8,350 @@ struct Race {
 }
 
 impl Race {
+    fn distance_for(&self, press: u64) -> u128 {
+        (self.time - press) as u128 * press as u128
+    }
+
+    // Pressing for p wins when p * (time - p) > distance, i.e. between the
+    // roots of p^2 - time * p + distance = 0. The integer square root puts the
+    // first winning press within a step of (time - sqrt) / 2, and the exact
+    // check settles ties, where the boat only matches the record. The winning
+    // presses are symmetric about time / 2.
     fn ways_to_beat_record(&self) -> u64 {
+        let (time, distance) = (self.time as u128, self.distance as u128);
+        if time * time <= 4 * distance {
+            return 0;
+        }
+        let root = (time * time - 4 * distance).isqrt();
+        let mut first = ((time - root) / 2) as u64;
+        while first > 0 && self.distance_for(first - 1) > distance {
+            first -= 1;
+        }
+        while first <= self.time / 2 && self.distance_for(first) <= distance {
+            first += 1;
+        }
+        if first > self.time / 2 {
+            return 0;
+        }
+        self.time - 2 * first + 1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseError {
+    // A line that isn't "Label: fields".
+    BadLine {
+        line: usize,
+    },
+    DuplicateRow {
+        label: String,
+    },
+    MissingRow {
+        label: String,
+    },
+    NotANumber {
+        label: String,
+        field: String,
+    },
+    ColumnCount {
+        label: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::BadLine { line } => {
+                write!(f, "Expected \"Label: numbers\" on line {}", line)
+            }
+            ParseError::DuplicateRow { label } => write!(f, "Row {:?} appears twice", label),
+            ParseError::MissingRow { label } => write!(f, "No row {:?}", label),
+            ParseError::NotANumber { label, field } => {
+                write!(f, "Row {:?}: {:?} is not a number", label, field)
+            }
+            ParseError::ColumnCount {
+                label,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Row {:?} has {} columns, expected {}",
+                label, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone)]
+struct Row {
+    label: String,
+    // The digits as written, for reading the row as one kerned number.
+    fields: Vec<String>,
+    values: Vec<u64>,
+}
+
+// The labelled rows of the race sheet. Any rows can be present, as long as
+// they all have the same number of columns, so extra rows need no changes
+// here.
+#[derive(Debug, Clone)]
+struct Sheet {
+    rows: Vec<Row>,
+}
+
+impl Sheet {
+    fn parse(lines: &[String]) -> Result<Sheet, ParseError> {
+        let mut rows: Vec<Row> = Vec::new();
+        for (n, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (label, fields) = line
+                .split_once(':')
+                .ok_or(ParseError::BadLine { line: n + 1 })?;
+            let label = label.trim().to_string();
+            if rows.iter().any(|r| r.label == label) {
+                return Err(ParseError::DuplicateRow { label });
+            }
+            let fields = fields
+                .split_whitespace()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>();
+            let values = fields
+                .iter()
+                .map(|f| {
+                    f.parse().map_err(|_| ParseError::NotANumber {
+                        label: label.clone(),
+                        field: f.clone(),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(first) = rows.first() {
+                if first.values.len() != values.len() {
+                    return Err(ParseError::ColumnCount {
+                        label,
+                        expected: first.values.len(),
+                        found: values.len(),
+                    });
+                }
+            }
+            rows.push(Row {
+                label,
+                fields,
+                values,
+            });
+        }
+        Ok(Sheet { rows })
+    }
+
+    fn row(&self, label: &str) -> Result<&Row, ParseError> {
+        self.rows
+            .iter()
+            .find(|r| r.label == label)
+            .ok_or_else(|| ParseError::MissingRow {
+                label: label.to_string(),
+            })
+    }
+
+    // The row read one number per column.
+    fn columns(&self, label: &str) -> Result<Vec<u64>, ParseError> {
+        Ok(self.row(label)?.values.clone())
+    }
+
+    // The row read as a single number, ignoring the spaces.
+    fn kerned(&self, label: &str) -> Result<u64, ParseError> {
+        let row = self.row(label)?;
+        let joined = row.fields.join("");
+        joined.parse().map_err(|_| ParseError::NotANumber {
+            label: row.label.clone(),
+            field: joined,
+        })
+    }
+
+    fn races(&self) -> Result<Vec<Race>, ParseError> {
+        Ok(self
+            .columns("Time")?
+            .into_iter()
+            .zip(self.columns("Distance")?)
+            .map(|(time, distance)| Race { time, distance })
+            .collect())
+    }
+
+    fn kerned_race(&self) -> Result<Race, ParseError> {
+        Ok(Race {
+            time: self.kerned("Time")?,
+            distance: self.kerned("Distance")?,
+        })
+    }
+}
+
+fn part1(lines: &[String]) -> Result<u64> {
+    Sheet::parse(lines)?
+        .races()?
+        .iter()
+        .try_fold(1_u64, |p, r| p.checked_mul(r.ways_to_beat_record()))
+        .ok_or_else(|| anyhow!("Product of the ways to win overflows"))
+}
+
+fn part2(lines: &[String]) -> Result<u64> {
+    Ok(Sheet::parse(lines)?.kerned_race()?.ways_to_beat_record())
+}
+
+fn main() -> Result<()> {
+    let lines = input_lines();
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|x| x.to_string()).collect()
+    }
+
+    fn example() -> Vec<String> {
+        lines(indoc! {"
+            Time:      7  15   30
+            Distance:  9  40  200
+        "})
+    }
+
+    fn brute_force(race: &Race) -> u64 {
         let mut ways = 0;
-        for press in 0..=self.time {
-            if (self.time - press) * press > self.distance {
+        for press in 0..=race.time {
+            if (race.time - press) * press > race.distance {
                 ways += 1;
             }
         }
         ways
     }
-}
 
-fn main() {
-    let lines = input_lines();
-    let races = lines[0]
-        .split_once(":")
-        .unwrap()
-        .1
-        .trim()
-        .split_whitespace()
-        .map(|x| x.parse().unwrap())
-        .zip(
-            lines[1]
-                .split_once(":")
-                .unwrap()
-                .1
-                .trim()
-                .split_whitespace()
-                .map(|x| x.parse().unwrap()),
-        )
-        .map(|(time, distance)| Race { time, distance })
-        .collect::<Vec<_>>();
-    // println!("{:?}", races);
-    let mut product_of_ways = 1;
-    for race in &races {
-        product_of_ways *= race.ways_to_beat_record();
-        // println!("{:?} {}", race, race.ways_to_beat_record());
-    }
-    println!("Part 1: {}", product_of_ways);
-
-    let race = Race {
-        time: lines[0]
-            .split_once(":")
-            .unwrap()
-            .1
-            .trim()
-            .split_whitespace()
-            .collect::<Vec<_>>()
-            .join("")
-            .parse()
-            .unwrap(),
-        distance: lines[1]
-            .split_once(":")
-            .unwrap()
-            .1
-            .trim()
-            .split_whitespace()
-            .collect::<Vec<_>>()
-            .join("")
-            .parse()
-            .unwrap(),
-    };
-    // println!("{:?}", race);
-    println!("Part 2: {}", race.ways_to_beat_record());
+    #[test]
+    fn test_examples() {
+        let ways = |time, distance| Race { time, distance }.ways_to_beat_record();
+        assert_eq!(ways(7, 9), 4);
+        assert_eq!(ways(15, 40), 8);
+        // Pressing for 10 or 20 only ties the record.
+        assert_eq!(ways(30, 200), 9);
+        assert_eq!(ways(71530, 940200), 71503);
+        assert_eq!(ways(10, 25), 0);
+        assert_eq!(ways(10, 24), 1);
+        assert_eq!(ways(0, 0), 0);
+        // Far beyond what a loop could manage.
+        assert_eq!(ways(u64::MAX, 0), u64::MAX - 1);
+        assert_eq!(ways(2_000_000_000, 1_000_000_000_000_000_000), 0);
+        assert_eq!(ways(2_000_000_000, 999_999_999_999_999_999), 1);
+        assert_eq!(ways(2_000_000_000, 999_999_999_000_000_000), 63245);
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        // Records spread up to just past the best possible distance, so some
+        // races are unwinnable.
+        for time in 0..200_u64 {
+            let best = time * time / 4;
+            let spread = (0..best).step_by(time as usize / 4 + 1);
+            for distance in spread.chain(best.saturating_sub(2)..best + 3) {
+                let race = Race { time, distance };
+                assert_eq!(race.ways_to_beat_record(), brute_force(&race), "{:?}", race);
+            }
+        }
+        // Every tie: distances that some press exactly matches.
+        for time in 0..60 {
+            for press in 0..=time {
+                let race = Race {
+                    time,
+                    distance: press * (time - press),
+                };
+                assert_eq!(race.ways_to_beat_record(), brute_force(&race), "{:?}", race);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sheet() -> Result<()> {
+        let sheet = Sheet::parse(&lines(indoc! {"
+            Time:      7  15   30
+            Distance:  9  40  200
+
+            Boost:    01   2    3
+        "}))?;
+        assert_eq!(sheet.columns("Time")?, [7, 15, 30]);
+        assert_eq!(sheet.kerned("Distance")?, 940200);
+        // Extra rows come along without any changes to the parser.
+        assert_eq!(sheet.columns("Boost")?, [1, 2, 3]);
+        assert_eq!(sheet.kerned("Boost")?, 123);
+        assert_eq!(
+            sheet.row("Wind").unwrap_err(),
+            ParseError::MissingRow {
+                label: "Wind".to_string()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |s: &str| Sheet::parse(&lines(s)).unwrap_err();
+        assert_eq!(
+            err("Time: 7 15\nDistance: 9"),
+            ParseError::ColumnCount {
+                label: "Distance".to_string(),
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(
+            err("Time: 7 x5"),
+            ParseError::NotANumber {
+                label: "Time".to_string(),
+                field: "x5".to_string()
+            }
+        );
+        assert_eq!(err("Time: 7\n9"), ParseError::BadLine { line: 2 });
+        assert_eq!(
+            err("Time: 7\nTime: 8"),
+            ParseError::DuplicateRow {
+                label: "Time".to_string()
+            }
+        );
+        assert_eq!(
+            err("Time: 7 15\nDistance: 9").to_string(),
+            "Row \"Distance\" has 1 columns, expected 2"
+        );
+        // Fine column by column, but too big kerned.
+        let sheet = Sheet::parse(&lines("Time: 9999999999 9999999999\nDistance: 1 2")).unwrap();
+        assert!(sheet.races().is_ok());
+        assert_eq!(
+            sheet.kerned_race().unwrap_err(),
+            ParseError::NotANumber {
+                label: "Time".to_string(),
+                field: "99999999999999999999".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&example())?, 288);
+        // Each race can be won 10^10 - 1 ways, and the product doesn't fit.
+        assert_eq!(part1(&lines("Time: 10000000000\nDistance: 0"))?, 9999999999);
+        let two = lines("Time: 10000000000 10000000000\nDistance: 0 0");
+        assert!(part1(&two).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&example())?, 71503);
+        Ok(())
+    }
 }
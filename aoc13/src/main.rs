@@ -1,9 +1,224 @@
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Reflection {
+    // Number of rows above the line.
+    Horizontal(usize),
+    // Number of columns left of the line.
+    Vertical(usize),
+}
+
+impl Reflection {
+    fn summary(&self) -> usize {
+        match self {
+            Reflection::Horizontal(rows) => 100 * rows,
+            Reflection::Vertical(cols) => *cols,
+        }
+    }
+}
+
+// Each row and column is stored as a bitmask, with rocks as 1 bits.
+#[derive(Debug)]
+struct Pattern {
+    rows: Vec<u64>,
+    cols: Vec<u64>,
+}
+
+impl Pattern {
+    fn parse(lines: &[String]) -> Result<Pattern> {
+        let width = lines.first().map_or(0, |l| l.len());
+        if width == 0 || width > 64 || lines.len() > 64 {
+            return Err(anyhow!(
+                "Pattern must be between 1x1 and 64x64, got {}x{}",
+                lines.len(),
+                width
+            ));
+        }
+        let mut rows = vec![0; lines.len()];
+        let mut cols = vec![0; width];
+        for (r, line) in lines.iter().enumerate() {
+            if line.len() != width {
+                return Err(anyhow!("Expected {} columns, got {:?}", width, line));
+            }
+            for (c, ch) in line.chars().enumerate() {
+                match ch {
+                    '#' => {
+                        rows[r] |= 1 << c;
+                        cols[c] |= 1 << r;
+                    }
+                    '.' => {}
+                    _ => return Err(anyhow!("Unexpected character {:?} in {:?}", ch, line)),
+                }
+            }
+        }
+        Ok(Pattern { rows, cols })
+    }
+
+    // Every reflection line with exactly `smudges` differing cells.
+    fn reflections(&self, smudges: u32) -> Vec<Reflection> {
+        mirror_lines(&self.rows, smudges)
+            .into_iter()
+            .map(Reflection::Horizontal)
+            .chain(
+                mirror_lines(&self.cols, smudges)
+                    .into_iter()
+                    .map(Reflection::Vertical),
+            )
+            .collect()
+    }
+
+    fn reflection(&self, smudges: u32) -> Option<Reflection> {
+        self.reflections(smudges).first().copied()
+    }
+}
+
+// Returns each split point `n` (the number of lines before the mirror) for
+// which the mirrored pairs differ in exactly `smudges` bits.
+fn mirror_lines(lines: &[u64], smudges: u32) -> Vec<usize> {
+    (1..lines.len())
+        .filter(|&n| {
+            let mut diff = 0;
+            for (a, b) in lines[..n].iter().rev().zip(&lines[n..]) {
+                diff += (a ^ b).count_ones();
+                if diff > smudges {
+                    return false;
+                }
+            }
+            diff == smudges
+        })
+        .collect()
+}
+
+fn parse(lines: &[String]) -> Result<Vec<Pattern>> {
+    lines
+        .split(|l| l.is_empty())
+        .filter(|p| !p.is_empty())
+        .map(Pattern::parse)
+        .collect()
+}
+
+fn summarize(lines: &[String], smudges: u32) -> Result<usize> {
+    let mut sum = 0;
+    for (idx, pattern) in parse(lines)?.iter().enumerate() {
+        sum += pattern
+            .reflection(smudges)
+            .ok_or_else(|| anyhow!("No reflection with {} smudges in pattern {}", smudges, idx))?
+            .summary();
+    }
+    Ok(sum)
+}
+
+fn part1(lines: &[String]) -> Result<usize> {
+    summarize(lines, 0)
+}
+
+fn part2(lines: &[String]) -> Result<usize> {
+    summarize(lines, 1)
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|a| a == "--all") {
+        // The number of smudges to look for can be given, like "--all 2",
+        // otherwise both parts' counts are shown.
+        let counts = match args.iter().find(|a| !a.starts_with("--")) {
+            Some(k) => vec![k
+                .parse()
+                .map_err(|e| anyhow!("Bad smudge count {:?}: {}", k, e))?],
+            None => vec![0, 1],
+        };
+        // Report every candidate, so ambiguous patterns stand out.
+        for (idx, pattern) in parse(&lines)?.iter().enumerate() {
+            for &smudges in &counts {
+                let candidates = pattern.reflections(smudges);
+                println!(
+                    "Pattern {} smudges {}: {:?}{}",
+                    idx,
+                    smudges,
+                    candidates,
+                    if candidates.len() > 1 {
+                        " (ambiguous)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+    }
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            #.##..##.
+            ..#.##.#.
+            ##......#
+            ##......#
+            ..#.##.#.
+            ..##..##.
+            #.#.##.#.
+
+            #...##..#
+            #....#..#
+            ..##..###
+            #####.##.
+            #####.##.
+            ..##..###
+            #....#..#
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_reflections() -> Result<()> {
+        let patterns = parse(&lines())?;
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(patterns[0].reflections(0), [Reflection::Vertical(5)]);
+        assert_eq!(patterns[1].reflections(0), [Reflection::Horizontal(4)]);
+        assert_eq!(patterns[0].reflections(1), [Reflection::Horizontal(3)]);
+        assert_eq!(patterns[1].reflections(1), [Reflection::Horizontal(1)]);
+        assert_eq!(patterns[0].reflections(64), []);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ambiguous() -> Result<()> {
+        let pattern = Pattern::parse(&["#..#".to_string(), "#..#".to_string()])?;
+        assert_eq!(
+            pattern.reflections(0),
+            [Reflection::Horizontal(1), Reflection::Vertical(2)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Pattern::parse(&["#.".to_string(), "#".to_string()]).is_err());
+        assert!(Pattern::parse(&["#x".to_string()]).is_err());
+        assert!(Pattern::parse(&[".".repeat(65)]).is_err());
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines())?, 405);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines())?, 400);
+        Ok(())
+    }
 }
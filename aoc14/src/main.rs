@@ -1,9 +1,262 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    North,
+    West,
+    South,
+    East,
+}
+
+const SPIN_CYCLE: [Direction; 4] = [
+    Direction::North,
+    Direction::West,
+    Direction::South,
+    Direction::East,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Platform {
+    grid: Vec<Vec<char>>,
+}
+
+impl Platform {
+    fn parse(lines: &[String]) -> Result<Platform> {
+        let grid = lines
+            .iter()
+            .map(|l| l.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        if grid.is_empty() || grid[0].is_empty() {
+            return Err(anyhow!("Empty platform"));
+        }
+        for row in &grid {
+            if row.len() != grid[0].len() {
+                return Err(anyhow!("Ragged platform row {:?}", row));
+            }
+            if let Some(c) = row.iter().find(|c| !matches!(c, 'O' | '#' | '.')) {
+                return Err(anyhow!("Unexpected character {:?}", c));
+            }
+        }
+        Ok(Platform { grid })
+    }
+
+    fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    fn width(&self) -> usize {
+        self.grid[0].len()
+    }
+
+    // Maps a position along a lane, counted from the edge the rocks roll
+    // towards, to grid coordinates.
+    fn cell(&self, dir: Direction, lane: usize, pos: usize) -> (usize, usize) {
+        match dir {
+            Direction::North => (pos, lane),
+            Direction::South => (self.height() - 1 - pos, lane),
+            Direction::West => (lane, pos),
+            Direction::East => (lane, self.width() - 1 - pos),
+        }
+    }
+
+    fn tilt(&mut self, dir: Direction) {
+        let (lanes, len) = match dir {
+            Direction::North | Direction::South => (self.width(), self.height()),
+            Direction::West | Direction::East => (self.height(), self.width()),
+        };
+        for lane in 0..lanes {
+            let mut free = 0;
+            for pos in 0..len {
+                let (r, c) = self.cell(dir, lane, pos);
+                match self.grid[r][c] {
+                    '#' => free = pos + 1,
+                    'O' => {
+                        self.grid[r][c] = '.';
+                        let (r, c) = self.cell(dir, lane, free);
+                        self.grid[r][c] = 'O';
+                        free += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn spin(&mut self) {
+        for dir in SPIN_CYCLE {
+            self.tilt(dir);
+        }
+    }
+
+    // Runs `cycles` spin cycles, skipping ahead once a repeated state is
+    // found. With `trace`, prints the load after each simulated cycle.
+    fn spin_cycles(&mut self, cycles: usize, trace: bool) {
+        let mut seen = HashMap::new();
+        let mut cycle = 0;
+        while cycle < cycles {
+            if let Some(start) = seen.insert(self.clone(), cycle) {
+                let period = cycle - start;
+                if trace {
+                    println!("Pre-period {}, period {}", start, period);
+                }
+                for _ in 0..(cycles - cycle) % period {
+                    self.spin();
+                }
+                return;
+            }
+            self.spin();
+            cycle += 1;
+            if trace {
+                println!("Cycle {}: load {}", cycle, self.north_load());
+            }
+        }
+    }
+
+    fn north_load(&self) -> usize {
+        self.grid
+            .iter()
+            .enumerate()
+            .map(|(r, row)| (self.height() - r) * row.iter().filter(|c| **c == 'O').count())
+            .sum()
+    }
+}
+
+fn part1(lines: &[String]) -> Result<usize> {
+    let mut platform = Platform::parse(lines)?;
+    platform.tilt(Direction::North);
+    Ok(platform.north_load())
+}
+
+fn part2(lines: &[String], trace: bool) -> Result<usize> {
+    let mut platform = Platform::parse(lines)?;
+    platform.spin_cycles(1_000_000_000, trace);
+    Ok(platform.north_load())
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    let trace = std::env::args().any(|a| a == "--trace");
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines, trace)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            O....#....
+            O.OO#....#
+            .....##...
+            OO.#O....O
+            .O.....O#.
+            O.#..O.#.#
+            ..O..#O..O
+            .......O..
+            #....###..
+            #OO..#....
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    fn platform(s: &str) -> Platform {
+        Platform::parse(&s.lines().map(|x| x.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_tilt() -> Result<()> {
+        let mut p = Platform::parse(&lines())?;
+        p.tilt(Direction::North);
+        assert_eq!(
+            p,
+            platform(indoc! {"
+                OOOO.#.O..
+                OO..#....#
+                OO..O##..O
+                O..#.OO...
+                ........#.
+                ..#....#.#
+                ..O..#.O.O
+                ..O.......
+                #....###..
+                #....#....
+            "})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_spin() -> Result<()> {
+        let mut p = Platform::parse(&lines())?;
+        p.spin();
+        assert_eq!(
+            p,
+            platform(indoc! {"
+                .....#....
+                ....#...O#
+                ...OO##...
+                .OO#......
+                .....OOO#.
+                .O#...O#.#
+                ....O#....
+                ......OOOO
+                #...O###..
+                #..OO#....
+            "})
+        );
+        p.spin();
+        p.spin();
+        assert_eq!(
+            p,
+            platform(indoc! {"
+                .....#....
+                ....#...O#
+                .....##...
+                ..O#......
+                .....OOO#.
+                .O#...O#.#
+                ....O#...O
+                .......OOO
+                #...O###.O
+                #.OOO#...O
+            "})
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_spin_cycles() -> Result<()> {
+        // Skipping ahead must agree with simulating every cycle.
+        for cycles in 0..40 {
+            let mut fast = Platform::parse(&lines())?;
+            fast.spin_cycles(cycles, false);
+            let mut slow = Platform::parse(&lines())?;
+            for _ in 0..cycles {
+                slow.spin();
+            }
+            assert_eq!(fast, slow, "after {} cycles", cycles);
+        }
+        Ok(())
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines())?, 136);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines(), false)?, 64);
+        Ok(())
+    }
 }
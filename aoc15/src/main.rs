@@ -1,9 +1,208 @@
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+fn hash(s: &str) -> usize {
+    s.bytes().fold(0, |acc, b| ((acc + b as usize) * 17) % 256)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Operation {
+    Remove,
+    Insert(u32),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Step<'a> {
+    raw: &'a str,
+    label: &'a str,
+    op: Operation,
+}
+
+impl<'a> Step<'a> {
+    fn parse(s: &'a str) -> Result<Step<'a>> {
+        let (label, op) = if let Some(label) = s.strip_suffix('-') {
+            (label, Operation::Remove)
+        } else if let Some((label, focal)) = s.split_once('=') {
+            (
+                label,
+                Operation::Insert(
+                    focal
+                        .parse()
+                        .map_err(|e| anyhow!("Bad focal length in {:?}: {}", s, e))?,
+                ),
+            )
+        } else {
+            return Err(anyhow!("Unrecognized step {:?}", s));
+        };
+        if label.is_empty() {
+            return Err(anyhow!("Missing label in step {:?}", s));
+        }
+        Ok(Step { raw: s, label, op })
+    }
+}
+
+// The initialization sequence may be wrapped across lines; newlines are ignored.
+fn sequence(lines: &[String]) -> String {
+    lines.concat()
+}
+
+fn steps(seq: &str) -> impl Iterator<Item = &str> {
+    seq.split(',').filter(|s| !s.is_empty())
+}
+
+struct Boxes<'a> {
+    boxes: Vec<Vec<(&'a str, u32)>>,
+}
+
+impl<'a> Boxes<'a> {
+    fn new() -> Boxes<'a> {
+        Boxes {
+            boxes: vec![Vec::new(); 256],
+        }
+    }
+
+    fn apply(&mut self, step: &Step<'a>) {
+        let lenses = &mut self.boxes[hash(step.label)];
+        let pos = lenses.iter().position(|(l, _)| *l == step.label);
+        match (&step.op, pos) {
+            (Operation::Remove, Some(pos)) => {
+                lenses.remove(pos);
+            }
+            (Operation::Remove, None) => {}
+            (Operation::Insert(focal), Some(pos)) => lenses[pos].1 = *focal,
+            (Operation::Insert(focal), None) => lenses.push((step.label, *focal)),
+        }
+    }
+
+    fn focusing_power(&self) -> usize {
+        self.boxes
+            .iter()
+            .enumerate()
+            .flat_map(|(b, lenses)| {
+                lenses
+                    .iter()
+                    .enumerate()
+                    .map(move |(slot, (_, focal))| (b + 1) * (slot + 1) * *focal as usize)
+            })
+            .sum()
+    }
+
+    // Formats the non-empty boxes the same way as the puzzle statement.
+    fn render(&self) -> String {
+        let mut s = String::new();
+        for (b, lenses) in self.boxes.iter().enumerate() {
+            if !lenses.is_empty() {
+                s.push_str(&format!("Box {}:", b));
+                for (label, focal) in lenses {
+                    s.push_str(&format!(" [{} {}]", label, focal));
+                }
+                s.push('\n');
+            }
+        }
+        s
+    }
+}
+
+fn part1(lines: &[String]) -> Result<usize> {
+    Ok(steps(&sequence(lines)).map(hash).sum())
+}
+
+// What --verbose prints after each step.
+fn trace_line(step: &Step, boxes: &Boxes) -> String {
+    format!("After {:?}:\n{}", step.raw, boxes.render())
+}
+
+fn part2(lines: &[String], verbose: bool) -> Result<usize> {
+    let seq = sequence(lines);
+    let mut boxes = Boxes::new();
+    for s in steps(&seq) {
+        let step = Step::parse(s)?;
+        boxes.apply(&step);
+        if verbose {
+            println!("{}", trace_line(&step, &boxes));
+        }
+    }
+    Ok(boxes.focusing_power())
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    let verbose = std::env::args().any(|a| a == "--verbose");
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines, verbose)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash("HASH"), 52);
+        assert_eq!(hash("rn=1"), 30);
+        assert_eq!(hash("rn"), 0);
+        assert_eq!(hash("qp"), 1);
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        assert_eq!(
+            Step::parse("rn=1")?,
+            Step {
+                raw: "rn=1",
+                label: "rn",
+                op: Operation::Insert(1)
+            }
+        );
+        assert_eq!(Step::parse("cm-")?.op, Operation::Remove);
+        assert!(Step::parse("cm").is_err());
+        assert!(Step::parse("cm=x").is_err());
+        assert!(Step::parse("=1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace() -> Result<()> {
+        let seq = sequence(&lines());
+        let mut boxes = Boxes::new();
+        let trace = steps(&seq)
+            .map(|s| {
+                let step = Step::parse(s)?;
+                boxes.apply(&step);
+                Ok(trace_line(&step, &boxes))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(trace[0], "After \"rn=1\":\nBox 0: [rn 1]\n");
+        assert_eq!(trace[1], "After \"cm-\":\nBox 0: [rn 1]\n");
+        assert_eq!(trace[2], "After \"qp=3\":\nBox 0: [rn 1]\nBox 1: [qp 3]\n");
+        assert_eq!(
+            trace[10],
+            "After \"ot=7\":\nBox 0: [rn 1] [cm 2]\nBox 3: [ot 7] [ab 5] [pc 6]\n"
+        );
+        Ok(())
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines())?, 1320);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines(), false)?, 145);
+        Ok(())
+    }
 }
@@ -1,9 +1,296 @@
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    // Bit used to record which directions a beam has passed through a cell in.
+    fn bit(&self) -> u8 {
+        match self {
+            Direction::Up => 1,
+            Direction::Down => 2,
+            Direction::Left => 4,
+            Direction::Right => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Beam {
+    r: usize,
+    c: usize,
+    dir: Direction,
+}
+
+struct Contraption {
+    grid: Vec<Vec<char>>,
+}
+
+impl Contraption {
+    fn parse(lines: &[String]) -> Result<Contraption> {
+        let grid = lines
+            .iter()
+            .map(|l| l.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        if grid.is_empty() || grid[0].is_empty() {
+            return Err(anyhow!("Empty contraption"));
+        }
+        for row in &grid {
+            if row.len() != grid[0].len() {
+                return Err(anyhow!("Ragged contraption row {:?}", row));
+            }
+            if let Some(c) = row
+                .iter()
+                .find(|c| !matches!(c, '.' | '/' | '\\' | '|' | '-'))
+            {
+                return Err(anyhow!("Unexpected character {:?}", c));
+            }
+        }
+        Ok(Contraption { grid })
+    }
+
+    fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    fn width(&self) -> usize {
+        self.grid[0].len()
+    }
+
+    fn step(&self, r: usize, c: usize, dir: Direction) -> Option<Beam> {
+        let (r, c) = match dir {
+            Direction::Up if r > 0 => (r - 1, c),
+            Direction::Down if r + 1 < self.height() => (r + 1, c),
+            Direction::Left if c > 0 => (r, c - 1),
+            Direction::Right if c + 1 < self.width() => (r, c + 1),
+            _ => return None,
+        };
+        Some(Beam { r, c, dir })
+    }
+
+    // Directions a beam leaves a cell in, given the direction it entered.
+    fn outgoing(tile: char, dir: Direction) -> Vec<Direction> {
+        use Direction::*;
+        match (tile, dir) {
+            ('/', Up) => vec![Right],
+            ('/', Down) => vec![Left],
+            ('/', Left) => vec![Down],
+            ('/', Right) => vec![Up],
+            ('\\', Up) => vec![Left],
+            ('\\', Down) => vec![Right],
+            ('\\', Left) => vec![Up],
+            ('\\', Right) => vec![Down],
+            ('|', Left | Right) => vec![Up, Down],
+            ('-', Up | Down) => vec![Left, Right],
+            _ => vec![dir],
+        }
+    }
+
+    // Traces all beams from `start`, returning for each cell the set of
+    // directions beams passed through it in. A beam that re-enters a cell in
+    // a direction already seen is a loop and is dropped.
+    fn trace(&self, start: Beam) -> Vec<Vec<u8>> {
+        let mut seen = vec![vec![0_u8; self.width()]; self.height()];
+        let mut beams = vec![start];
+        while let Some(Beam { r, c, dir }) = beams.pop() {
+            if seen[r][c] & dir.bit() != 0 {
+                continue;
+            }
+            seen[r][c] |= dir.bit();
+            for out in Contraption::outgoing(self.grid[r][c], dir) {
+                if let Some(next) = self.step(r, c, out) {
+                    beams.push(next);
+                }
+            }
+        }
+        seen
+    }
+
+    fn energized(&self, start: Beam) -> usize {
+        self.trace(start)
+            .iter()
+            .flatten()
+            .filter(|dirs| **dirs != 0)
+            .count()
+    }
+
+    fn render(&self, start: Beam) -> String {
+        let mut s = String::new();
+        for row in self.trace(start) {
+            s.extend(row.iter().map(|dirs| if *dirs != 0 { '#' } else { '.' }));
+            s.push('\n');
+        }
+        s
+    }
+
+    fn entries(&self) -> Vec<Beam> {
+        let (h, w) = (self.height(), self.width());
+        let mut v = Vec::new();
+        for r in 0..h {
+            v.push(Beam {
+                r,
+                c: 0,
+                dir: Direction::Right,
+            });
+            v.push(Beam {
+                r,
+                c: w - 1,
+                dir: Direction::Left,
+            });
+        }
+        for c in 0..w {
+            v.push(Beam {
+                r: 0,
+                c,
+                dir: Direction::Down,
+            });
+            v.push(Beam {
+                r: h - 1,
+                c,
+                dir: Direction::Up,
+            });
+        }
+        v
+    }
+
+    // Tries every edge entry, spreading them across the available threads.
+    fn best_entry(&self) -> (Beam, usize) {
+        let entries = self.entries();
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = entries.len().div_ceil(threads);
+        std::thread::scope(|s| {
+            entries
+                .chunks(chunk)
+                .map(|chunk| {
+                    s.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|b| (*b, self.energized(*b)))
+                            .max_by_key(|(_, n)| *n)
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .filter_map(|h| h.join().unwrap())
+                .max_by_key(|(_, n)| *n)
+                .unwrap()
+        })
+    }
+}
+
+const TOP_LEFT: Beam = Beam {
+    r: 0,
+    c: 0,
+    dir: Direction::Right,
+};
+
+fn part1(lines: &[String]) -> Result<usize> {
+    Ok(Contraption::parse(lines)?.energized(TOP_LEFT))
+}
+
+fn part2(lines: &[String]) -> Result<usize> {
+    Ok(Contraption::parse(lines)?.best_entry().1)
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    if std::env::args().any(|a| a == "--render") {
+        print!("{}", Contraption::parse(&lines)?.render(TOP_LEFT));
+    }
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {r"
+            .|...\....
+            |.-.\.....
+            .....|-...
+            ........|.
+            ..........
+            .........\
+            ..../.\\..
+            .-.-/..|..
+            .|....-|.\
+            ..//.|....
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        assert_eq!(
+            Contraption::parse(&lines())?.render(TOP_LEFT),
+            indoc! {"
+                ######....
+                .#...#....
+                .#...#####
+                .#...##...
+                .#...##...
+                .#...##...
+                .#..####..
+                ########..
+                .#######..
+                .#...#.#..
+            "}
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_loop() -> Result<()> {
+        let c = Contraption::parse(&[r"/-\".to_string(), r"\-/".to_string()])?;
+        assert_eq!(
+            c.energized(Beam {
+                r: 0,
+                c: 1,
+                dir: Direction::Right
+            }),
+            6
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_entry() -> Result<()> {
+        assert_eq!(
+            Contraption::parse(&lines())?.best_entry(),
+            (
+                Beam {
+                    r: 0,
+                    c: 3,
+                    dir: Direction::Down
+                },
+                51
+            )
+        );
+        Ok(())
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines())?, 46);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines())?, 51);
+        Ok(())
+    }
 }
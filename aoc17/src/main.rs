@@ -1,9 +1,354 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Heading {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+const HEADINGS: [Heading; 4] = [Heading::Up, Heading::Down, Heading::Left, Heading::Right];
+
+impl Heading {
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    fn reverse(&self) -> Heading {
+        match self {
+            Heading::Up => Heading::Down,
+            Heading::Down => Heading::Up,
+            Heading::Left => Heading::Right,
+            Heading::Right => Heading::Left,
+        }
+    }
+
+    fn arrow(&self) -> char {
+        match self {
+            Heading::Up => '^',
+            Heading::Down => 'v',
+            Heading::Left => '<',
+            Heading::Right => '>',
+        }
+    }
+}
+
+// A search node: the crucible is at (r, c), arrived heading `heading`, and has
+// moved `run` blocks in a straight line so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    r: usize,
+    c: usize,
+    heading: Heading,
+    run: usize,
+}
+
+struct HeatMap {
+    grid: Vec<Vec<u32>>,
+}
+
+impl HeatMap {
+    fn parse(lines: &[String]) -> Result<HeatMap> {
+        let grid = lines
+            .iter()
+            .map(|l| {
+                l.chars()
+                    .map(|c| {
+                        c.to_digit(10)
+                            .ok_or_else(|| anyhow!("Unexpected character {:?} in {:?}", c, l))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if grid.is_empty() || grid[0].is_empty() {
+            return Err(anyhow!("Empty heat map"));
+        }
+        if grid.iter().any(|row| row.len() != grid[0].len()) {
+            return Err(anyhow!("Ragged heat map"));
+        }
+        Ok(HeatMap { grid })
+    }
+
+    fn height(&self) -> usize {
+        self.grid.len()
+    }
+
+    fn width(&self) -> usize {
+        self.grid[0].len()
+    }
+
+    fn step(&self, s: &State, heading: Heading) -> Option<(usize, usize)> {
+        match heading {
+            Heading::Up if s.r > 0 => Some((s.r - 1, s.c)),
+            Heading::Down if s.r + 1 < self.height() => Some((s.r + 1, s.c)),
+            Heading::Left if s.c > 0 => Some((s.r, s.c - 1)),
+            Heading::Right if s.c + 1 < self.width() => Some((s.r, s.c + 1)),
+            _ => None,
+        }
+    }
+
+    // Finds the path from the top-left to the bottom-right block with the
+    // least heat loss, where the crucible must move at least `min_run` and at
+    // most `max_run` blocks before turning. Returns the heat loss and each
+    // state along the path, excluding the start.
+    fn best_path(&self, min_run: usize, max_run: usize) -> Result<(u32, Vec<State>)> {
+        if min_run == 0 || min_run > max_run {
+            return Err(anyhow!("Invalid run limits {}..={}", min_run, max_run));
+        }
+        let index = |s: &State| {
+            ((s.r * self.width() + s.c) * 4 + s.heading.index()) * (max_run + 1) + s.run
+        };
+        let state = |idx: usize| State {
+            r: idx / (max_run + 1) / 4 / self.width(),
+            c: idx / (max_run + 1) / 4 % self.width(),
+            heading: HEADINGS[idx / (max_run + 1) % 4],
+            run: idx % (max_run + 1),
+        };
+        let size = self.height() * self.width() * 4 * (max_run + 1);
+        let mut dist = vec![u32::MAX; size];
+        let mut prev: Vec<Option<State>> = vec![None; size];
+        let mut queue = BinaryHeap::new();
+        // The crucible hasn't moved yet, so it may set off in either direction.
+        for heading in [Heading::Right, Heading::Down] {
+            let start = State {
+                r: 0,
+                c: 0,
+                heading,
+                run: 0,
+            };
+            dist[index(&start)] = 0;
+            queue.push(Reverse((0, index(&start))));
+        }
+        while let Some(Reverse((d, idx))) = queue.pop() {
+            if d > dist[idx] {
+                continue;
+            }
+            let s = state(idx);
+            // Only the start has a run of 0, so the goal is reached without
+            // moving when the map is a single block.
+            let stopped = s.run >= min_run || s.run == 0;
+            if s.r == self.height() - 1 && s.c == self.width() - 1 && stopped {
+                let mut path = vec![s];
+                while let Some(p) = prev[index(path.last().unwrap())] {
+                    path.push(p);
+                }
+                path.pop();
+                path.reverse();
+                return Ok((d, path));
+            }
+            for heading in HEADINGS {
+                if heading == s.heading.reverse() {
+                    continue;
+                }
+                let run = if heading == s.heading {
+                    s.run + 1
+                } else if s.run >= min_run {
+                    1
+                } else {
+                    continue;
+                };
+                if run > max_run {
+                    continue;
+                }
+                if let Some((r, c)) = self.step(&s, heading) {
+                    let next = State { r, c, heading, run };
+                    let nd = d + self.grid[r][c];
+                    let nidx = index(&next);
+                    if nd < dist[nidx] {
+                        dist[nidx] = nd;
+                        prev[nidx] = Some(s);
+                        queue.push(Reverse((nd, nidx)));
+                    }
+                }
+            }
+        }
+        Err(anyhow!("No path with run limits {}..={}", min_run, max_run))
+    }
+
+    // Overlays the path on the heat map, like the puzzle statement.
+    fn render(&self, path: &[State]) -> String {
+        let mut chars = self
+            .grid
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|h| char::from_digit(*h, 10).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for s in path {
+            chars[s.r][s.c] = s.heading.arrow();
+        }
+        let mut out = String::new();
+        for row in chars {
+            out.extend(row);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn part1(lines: &[String]) -> Result<u32> {
+    Ok(HeatMap::parse(lines)?.best_path(1, 3)?.0)
+}
+
+fn part2(lines: &[String]) -> Result<u32> {
+    Ok(HeatMap::parse(lines)?.best_path(4, 10)?.0)
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    if std::env::args().any(|a| a == "--render") {
+        let map = HeatMap::parse(&lines)?;
+        for (min_run, max_run) in [(1, 3), (4, 10)] {
+            print!("{}", map.render(&map.best_path(min_run, max_run)?.1));
+            println!();
+        }
+    }
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            2413432311323
+            3215453535623
+            3255245654254
+            3446585845452
+            4546657867536
+            1438598798454
+            4457876987766
+            3637877979653
+            4654967986887
+            4564679986453
+            1224686865563
+            2546548887735
+            4322674655533
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    fn check_path(map: &HeatMap, min_run: usize, max_run: usize) -> Result<()> {
+        let (loss, path) = map.best_path(min_run, max_run)?;
+        assert_eq!(loss, path.iter().map(|s| map.grid[s.r][s.c]).sum::<u32>());
+        let last = path.last().unwrap();
+        assert_eq!((last.r, last.c), (map.height() - 1, map.width() - 1));
+        for pair in path.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            assert_eq!(a.r.abs_diff(b.r) + a.c.abs_diff(b.c), 1);
+            if a.heading != b.heading {
+                assert!(a.run >= min_run && b.run == 1);
+            }
+            assert!(b.run <= max_run);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_path() -> Result<()> {
+        let map = HeatMap::parse(&lines())?;
+        check_path(&map, 1, 3)?;
+        check_path(&map, 4, 10)?;
+        // The path drawn in the puzzle statement, except for an equally good
+        // step right a row earlier near the top right.
+        assert_eq!(
+            map.render(&map.best_path(1, 3)?.1),
+            indoc! {"
+                2>>34^>>>1323
+                32v>>>35v>623
+                325524565v>54
+                3446585845v52
+                4546657867v>6
+                14385987984v4
+                44578769877v6
+                36378779796v>
+                465496798688v
+                456467998645v
+                12246868655<v
+                25465488877v5
+                43226746555v>
+            "}
+        );
+        let statement = indoc! {"
+            2>>34^>>>1323
+            32v>>>35v5623
+            32552456v>>54
+            3446585845v52
+            4546657867v>6
+            14385987984v4
+            44578769877v6
+            36378779796v>
+            465496798688v
+            456467998645v
+            12246868655<v
+            25465488877v5
+            43226746555v>
+        "};
+        let loss = statement
+            .lines()
+            .zip(lines())
+            .flat_map(|(drawn, row)| drawn.chars().zip(row.chars()).collect::<Vec<_>>())
+            .filter(|(drawn, _)| !drawn.is_ascii_digit())
+            .map(|(_, heat)| heat.to_digit(10).unwrap())
+            .sum::<u32>();
+        assert_eq!(loss, 102);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unfortunate() -> Result<()> {
+        let map = HeatMap::parse(
+            &indoc! {"
+                111111111111
+                999999999991
+                999999999991
+                999999999991
+                999999999991
+            "}
+            .lines()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>(),
+        )?;
+        assert_eq!(map.best_path(4, 10)?.0, 71);
+        check_path(&map, 4, 10)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_path() -> Result<()> {
+        let map = HeatMap::parse(&["12".to_string()])?;
+        assert!(map.best_path(4, 10).is_err());
+        assert!(map.best_path(3, 2).is_err());
+        assert_eq!(map.best_path(1, 3)?.0, 2);
+        // Already at the factory.
+        let map = HeatMap::parse(&["5".to_string()])?;
+        assert_eq!(map.best_path(4, 10)?, (0, Vec::new()));
+        Ok(())
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines())?, 102);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines())?, 94);
+        Ok(())
+    }
 }
@@ -1,9 +1,330 @@
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn delta(&self) -> (i128, i128) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Dig {
+    dir: Direction,
+    len: i128,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    // "R 6 (#70c710)": direction and length up front.
+    Plain,
+    // The color is the instruction: five hex digits of length, then the
+    // direction as 0-3 for R, D, L, U.
+    Color,
+}
+
+impl Dig {
+    fn parse(line: &str, encoding: Encoding) -> Result<Dig> {
+        let mut iter = line.split_whitespace();
+        let (dir, len, color) = match (iter.next(), iter.next(), iter.next(), iter.next()) {
+            (Some(dir), Some(len), Some(color), None) => (dir, len, color),
+            _ => return Err(anyhow!("Expected \"DIR LEN (#COLOR)\", got {:?}", line)),
+        };
+        let dig = match encoding {
+            Encoding::Plain => Dig {
+                dir: match dir {
+                    "U" => Direction::Up,
+                    "D" => Direction::Down,
+                    "L" => Direction::Left,
+                    "R" => Direction::Right,
+                    _ => return Err(anyhow!("Unknown direction {:?} in {:?}", dir, line)),
+                },
+                len: len
+                    .parse()
+                    .map_err(|e| anyhow!("Bad length in {:?}: {}", line, e))?,
+            },
+            Encoding::Color => {
+                let hex = color
+                    .strip_prefix("(#")
+                    .and_then(|c| c.strip_suffix(')'))
+                    .filter(|c| c.len() == 6 && c.is_ascii())
+                    .ok_or_else(|| anyhow!("Bad color {:?} in {:?}", color, line))?;
+                Dig {
+                    dir: match &hex[5..] {
+                        "0" => Direction::Right,
+                        "1" => Direction::Down,
+                        "2" => Direction::Left,
+                        "3" => Direction::Up,
+                        d => return Err(anyhow!("Unknown direction {:?} in {:?}", d, line)),
+                    },
+                    len: i128::from_str_radix(&hex[..5], 16)
+                        .map_err(|e| anyhow!("Bad length in {:?}: {}", line, e))?,
+                }
+            }
+        };
+        if dig.len < 0 {
+            return Err(anyhow!("Negative length in {:?}", line));
+        }
+        Ok(dig)
+    }
+}
+
+struct Plan {
+    digs: Vec<Dig>,
+}
+
+impl Plan {
+    fn parse(lines: &[String], encoding: Encoding) -> Result<Plan> {
+        Ok(Plan {
+            digs: lines
+                .iter()
+                .map(|l| Dig::parse(l, encoding))
+                .collect::<Result<Vec<_>>>()?,
+        })
+    }
+
+    // The trench corners as (row, col), starting and ending at the origin.
+    fn vertices(&self) -> Vec<(i128, i128)> {
+        let mut v = vec![(0, 0)];
+        for dig in &self.digs {
+            let (r, c) = *v.last().unwrap();
+            let (dr, dc) = dig.dir.delta();
+            v.push((r + dr * dig.len, c + dc * dig.len));
+        }
+        v
+    }
+
+    // Cubic meters of lava held: the polygon's area by the shoelace formula,
+    // plus the half of the one-meter trench that lies outside it (Pick's
+    // theorem).
+    fn volume(&self) -> Result<i128> {
+        let v = self.vertices();
+        if v.last() != v.first() {
+            return Err(anyhow!("Dig plan doesn't return to its start"));
+        }
+        let twice_area = v
+            .windows(2)
+            .map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1)
+            .sum::<i128>()
+            .abs();
+        let boundary = self.digs.iter().map(|d| d.len).sum::<i128>();
+        Ok((twice_area + boundary) / 2 + 1)
+    }
+
+    // Draws the dug-out lagoon if its bounding box fits in `max_width` by
+    // `max_height` cells. With `filled`, the interior is dug out too.
+    fn render(&self, max_width: usize, max_height: usize, filled: bool) -> Option<String> {
+        let v = self.vertices();
+        let rmin = v.iter().map(|p| p.0).min()?;
+        let rmax = v.iter().map(|p| p.0).max()?;
+        let cmin = v.iter().map(|p| p.1).min()?;
+        let cmax = v.iter().map(|p| p.1).max()?;
+        let height = usize::try_from(rmax - rmin + 1).ok()?;
+        let width = usize::try_from(cmax - cmin + 1).ok()?;
+        if width > max_width || height > max_height {
+            return None;
+        }
+        // Pad by one cell on each side so the outside is connected.
+        let mut grid = vec![vec!['.'; width + 2]; height + 2];
+        let (mut r, mut c) = ((1 - rmin) as usize, (1 - cmin) as usize);
+        for dig in &self.digs {
+            for _ in 0..dig.len {
+                grid[r][c] = '#';
+                let (dr, dc) = dig.dir.delta();
+                r = (r as i128 + dr) as usize;
+                c = (c as i128 + dc) as usize;
+            }
+        }
+        if filled {
+            let mut outside = vec![vec![false; width + 2]; height + 2];
+            let mut stack = vec![(0, 0)];
+            while let Some((r, c)) = stack.pop() {
+                if outside[r][c] || grid[r][c] == '#' {
+                    continue;
+                }
+                outside[r][c] = true;
+                if r > 0 {
+                    stack.push((r - 1, c));
+                }
+                if r + 1 < height + 2 {
+                    stack.push((r + 1, c));
+                }
+                if c > 0 {
+                    stack.push((r, c - 1));
+                }
+                if c + 1 < width + 2 {
+                    stack.push((r, c + 1));
+                }
+            }
+            for (row, out) in grid.iter_mut().zip(outside) {
+                for (cell, out) in row.iter_mut().zip(out) {
+                    if !out {
+                        *cell = '#';
+                    }
+                }
+            }
+        }
+        let mut s = String::new();
+        for row in &grid[1..=height] {
+            s.extend(&row[1..=width]);
+            s.push('\n');
+        }
+        Some(s)
+    }
+}
+
+fn part1(lines: &[String]) -> Result<i128> {
+    Plan::parse(lines, Encoding::Plain)?.volume()
+}
+
+fn part2(lines: &[String]) -> Result<i128> {
+    Plan::parse(lines, Encoding::Color)?.volume()
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    if std::env::args().any(|a| a == "--render") {
+        let env_or = |name, default| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        let (width, height) = (env_or("COLUMNS", 80), env_or("LINES", 24));
+        for encoding in [Encoding::Plain, Encoding::Color] {
+            match Plan::parse(&lines, encoding)?.render(width, height, true) {
+                Some(s) => println!("{}", s),
+                None => println!("{:?} plan is too large to render\n", encoding),
+            }
+        }
+    }
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            R 6 (#70c710)
+            D 5 (#0dc571)
+            L 2 (#5713f0)
+            D 2 (#d2c081)
+            R 2 (#59c680)
+            D 2 (#411b91)
+            L 5 (#8ceee2)
+            U 2 (#caa173)
+            L 1 (#1b58a2)
+            U 2 (#caa171)
+            R 2 (#7807d2)
+            U 3 (#a77fa3)
+            L 2 (#015232)
+            U 2 (#7a21e3)
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        assert_eq!(
+            Dig::parse("R 6 (#70c710)", Encoding::Plain)?,
+            Dig {
+                dir: Direction::Right,
+                len: 6
+            }
+        );
+        assert_eq!(
+            Dig::parse("R 6 (#70c710)", Encoding::Color)?,
+            Dig {
+                dir: Direction::Right,
+                len: 461937
+            }
+        );
+        assert!(Dig::parse("X 6 (#70c710)", Encoding::Plain).is_err());
+        assert!(Dig::parse("R 6 (#70c714)", Encoding::Color).is_err());
+        assert!(Dig::parse("R 6", Encoding::Plain).is_err());
+        assert!(Dig::parse("R -6 (#70c710)", Encoding::Plain).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let plan = Plan::parse(&lines(), Encoding::Plain)?;
+        assert_eq!(
+            plan.render(7, 10, false).unwrap(),
+            indoc! {"
+                #######
+                #.....#
+                ###...#
+                ..#...#
+                ..#...#
+                ###.###
+                #...#..
+                ##..###
+                .#....#
+                .######
+            "}
+        );
+        assert_eq!(
+            plan.render(7, 10, true).unwrap(),
+            indoc! {"
+                #######
+                #######
+                #######
+                ..#####
+                ..#####
+                #######
+                #####..
+                #######
+                .######
+                .######
+            "}
+        );
+        assert!(plan.render(6, 10, true).is_none());
+        assert!(Plan::parse(&lines(), Encoding::Color)?
+            .render(80, 24, true)
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_plan() -> Result<()> {
+        assert!(
+            Plan::parse(&["R 6 (#70c710)".to_string()], Encoding::Plain)?
+                .volume()
+                .is_err()
+        );
+        Ok(())
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines())?, 62);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines())?, 952408144115);
+        Ok(())
+    }
 }
@@ -1,9 +1,391 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+type Part = [u64; 4];
+
+// Inclusive (min, max) bounds for each of x, m, a and s.
+type Box = [(u64, u64); 4];
+
+fn category(s: &str) -> Result<usize> {
+    match s {
+        "x" => Ok(0),
+        "m" => Ok(1),
+        "a" => Ok(2),
+        "s" => Ok(3),
+        _ => Err(anyhow!("Unknown category {:?}", s)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    Accept,
+    Reject,
+    Workflow(String),
+}
+
+impl Target {
+    fn parse(s: &str) -> Target {
+        match s {
+            "A" => Target::Accept,
+            "R" => Target::Reject,
+            _ => Target::Workflow(s.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    Less(usize, u64),
+    Greater(usize, u64),
+}
+
+impl Condition {
+    fn matches(&self, part: &Part) -> bool {
+        match *self {
+            Condition::Less(cat, val) => part[cat] < val,
+            Condition::Greater(cat, val) => part[cat] > val,
+        }
+    }
+
+    // Splits a box into the part matching the condition and the rest. Nothing
+    // is less than 0 or greater than u64::MAX, so those match nothing.
+    fn split(&self, b: &Box) -> (Option<Box>, Option<Box>) {
+        let (cat, matching, rest) = match *self {
+            Condition::Less(cat, val) => (
+                cat,
+                val.checked_sub(1)
+                    .map(|below| (b[cat].0, std::cmp::min(b[cat].1, below))),
+                (std::cmp::max(b[cat].0, val), b[cat].1),
+            ),
+            Condition::Greater(cat, val) => (
+                cat,
+                val.checked_add(1)
+                    .map(|above| (std::cmp::max(b[cat].0, above), b[cat].1)),
+                (b[cat].0, std::cmp::min(b[cat].1, val)),
+            ),
+        };
+        let with = |range: Option<(u64, u64)>| match range {
+            Some((lo, hi)) if lo <= hi => {
+                let mut b = *b;
+                b[cat] = (lo, hi);
+                Some(b)
+            }
+            _ => None,
+        };
+        (with(matching), with(Some(rest)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    cond: Option<Condition>,
+    target: Target,
+}
+
+impl Rule {
+    fn parse(s: &str) -> Result<Rule> {
+        let Some((cond, target)) = s.split_once(':') else {
+            return Ok(Rule {
+                cond: None,
+                target: Target::parse(s),
+            });
+        };
+        let op = cond
+            .find(['<', '>'])
+            .ok_or_else(|| anyhow!("Missing comparison in rule {:?}", s))?;
+        let cat = category(&cond[..op])?;
+        let val = cond[op + 1..]
+            .parse()
+            .map_err(|e| anyhow!("Bad value in rule {:?}: {}", s, e))?;
+        Ok(Rule {
+            cond: Some(if &cond[op..=op] == "<" {
+                Condition::Less(cat, val)
+            } else {
+                Condition::Greater(cat, val)
+            }),
+            target: Target::parse(target),
+        })
+    }
+}
+
+struct System {
+    workflows: HashMap<String, Vec<Rule>>,
+    parts: Vec<Part>,
+}
+
+impl System {
+    fn parse(lines: &[String]) -> Result<System> {
+        let mut iter = lines.split(|l| l.is_empty());
+        let mut workflows = HashMap::new();
+        for line in iter.next().unwrap_or_default() {
+            let (name, rules) = line
+                .strip_suffix('}')
+                .and_then(|l| l.split_once('{'))
+                .ok_or_else(|| anyhow!("Bad workflow {:?}", line))?;
+            let rules = rules
+                .split(',')
+                .map(Rule::parse)
+                .collect::<Result<Vec<_>>>()?;
+            if rules.last().is_none_or(|r| r.cond.is_some()) {
+                return Err(anyhow!("Workflow {:?} has no fallback rule", name));
+            }
+            if workflows.insert(name.to_string(), rules).is_some() {
+                return Err(anyhow!("Duplicate workflow {:?}", name));
+            }
+        }
+        let mut parts = Vec::new();
+        for line in iter.next().unwrap_or_default() {
+            let mut part = [None; 4];
+            for rating in line
+                .strip_prefix('{')
+                .and_then(|l| l.strip_suffix('}'))
+                .ok_or_else(|| anyhow!("Bad part {:?}", line))?
+                .split(',')
+            {
+                let (cat, val) = rating
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("Bad rating {:?} in {:?}", rating, line))?;
+                part[category(cat)?] = Some(
+                    val.parse()
+                        .map_err(|e| anyhow!("Bad rating {:?} in {:?}: {}", rating, line, e))?,
+                );
+            }
+            match part {
+                [Some(x), Some(m), Some(a), Some(s)] => parts.push([x, m, a, s]),
+                _ => return Err(anyhow!("Missing rating in {:?}", line)),
+            }
+        }
+        Ok(System { workflows, parts })
+    }
+
+    fn workflow(&self, name: &str) -> Result<&Vec<Rule>> {
+        self.workflows
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown workflow {:?}", name))
+    }
+
+    fn accepts(&self, part: &Part) -> Result<bool> {
+        let mut name = "in";
+        // A part can visit each workflow at most once unless they loop.
+        for _ in 0..=self.workflows.len() {
+            let rule = self
+                .workflow(name)?
+                .iter()
+                .find(|r| r.cond.is_none_or(|c| c.matches(part)))
+                .unwrap();
+            match &rule.target {
+                Target::Accept => return Ok(true),
+                Target::Reject => return Ok(false),
+                Target::Workflow(next) => name = next,
+            }
+        }
+        Err(anyhow!("Workflows loop for part {:?}", part))
+    }
+
+    // Number of rating combinations within `bounds` that are accepted.
+    fn count_accepted(&self, bounds: RangeInclusive<u64>) -> Result<u128> {
+        let b = [(*bounds.start(), *bounds.end()); 4];
+        if bounds.is_empty() {
+            return Ok(0);
+        }
+        self.count_box("in", b, 0)
+    }
+
+    fn count_box(&self, name: &str, mut b: Box, depth: usize) -> Result<u128> {
+        if depth > self.workflows.len() {
+            return Err(anyhow!("Workflows loop through {:?}", name));
+        }
+        let mut count = 0;
+        for rule in self.workflow(name)? {
+            let (matching, rest) = match rule.cond {
+                Some(cond) => cond.split(&b),
+                None => (Some(b), None),
+            };
+            if let Some(matching) = matching {
+                let accepted = match &rule.target {
+                    Target::Accept => matching
+                        .iter()
+                        .try_fold(1_u128, |n, (lo, hi)| n.checked_mul((hi - lo) as u128 + 1))
+                        .ok_or_else(|| anyhow!("Count of accepted combinations overflows"))?,
+                    Target::Reject => 0,
+                    Target::Workflow(next) => self.count_box(next, matching, depth + 1)?,
+                };
+                count = accepted
+                    .checked_add(count)
+                    .ok_or_else(|| anyhow!("Count of accepted combinations overflows"))?;
+            }
+            match rest {
+                Some(rest) => b = rest,
+                None => break,
+            }
+        }
+        Ok(count)
+    }
+}
+
+fn part1(lines: &[String]) -> Result<u64> {
+    let system = System::parse(lines)?;
+    let mut sum = 0;
+    for part in &system.parts {
+        if system.accepts(part)? {
+            sum += part.iter().sum::<u64>();
+        }
+    }
+    Ok(sum)
+}
+
+fn part2(lines: &[String], bounds: RangeInclusive<u64>) -> Result<u128> {
+    System::parse(lines)?.count_accepted(bounds)
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    let max = match std::env::args().nth(1) {
+        Some(max) => max.parse()?,
+        None => 4000,
+    };
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines, 1..=max)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            px{a<2006:qkq,m>2090:A,rfg}
+            pv{a>1716:R,A}
+            lnx{m>1548:A,A}
+            rfg{s<537:gd,x>2440:R,A}
+            qs{s>3448:A,lnx}
+            qkq{x<1416:A,crn}
+            crn{x>2662:A,R}
+            in{s<1351:px,qqz}
+            qqz{s>2770:qs,m<1801:hdj,R}
+            gd{a>3333:R,R}
+            hdj{m>838:A,pv}
+
+            {x=787,m=2655,a=1222,s=2876}
+            {x=1679,m=44,a=2067,s=496}
+            {x=2036,m=264,a=79,s=2244}
+            {x=2461,m=1339,a=466,s=291}
+            {x=2127,m=1623,a=2188,s=1013}
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    fn system(s: &str) -> System {
+        System::parse(&s.lines().map(|x| x.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        assert_eq!(
+            Rule::parse("a<2006:qkq")?,
+            Rule {
+                cond: Some(Condition::Less(2, 2006)),
+                target: Target::Workflow("qkq".to_string())
+            }
+        );
+        assert_eq!(
+            Rule::parse("A")?,
+            Rule {
+                cond: None,
+                target: Target::Accept
+            }
+        );
+        assert!(Rule::parse("q<1:A").is_err());
+        assert!(Rule::parse("a=1:A").is_err());
+        let s = System::parse(&lines())?;
+        assert_eq!(s.workflows.len(), 11);
+        assert_eq!(s.parts[0], [787, 2655, 1222, 2876]);
+        assert!(System::parse(&["in{a<1:A}".to_string()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split() {
+        let b = [(1, 10); 4];
+        let (m, r) = Condition::Less(1, 4).split(&b);
+        assert_eq!(m, Some([(1, 10), (1, 3), (1, 10), (1, 10)]));
+        assert_eq!(r, Some([(1, 10), (4, 10), (1, 10), (1, 10)]));
+        let (m, r) = Condition::Greater(0, 10).split(&b);
+        assert_eq!(m, None);
+        assert_eq!(r, Some(b));
+        let (m, r) = Condition::Less(0, 1).split(&b);
+        assert_eq!(m, None);
+        assert_eq!(r, Some(b));
+
+        // Nothing is below 0 or above u64::MAX.
+        let b = [(0, u64::MAX); 4];
+        assert_eq!(Condition::Less(0, 0).split(&b), (None, Some(b)));
+        assert_eq!(Condition::Greater(0, u64::MAX).split(&b), (None, Some(b)));
+        assert_eq!(system("in{x<0:A,R}").count_accepted(0..=3).unwrap(), 0);
+        assert_eq!(system("in{x>3:A,R}").count_accepted(0..=3).unwrap(), 0);
+        let max = system("in{x>18446744073709551615:A,R}");
+        assert_eq!(max.count_accepted(0..=3).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_count_bounds() {
+        // Brute force over a small rating range.
+        let s = system(indoc! {"
+            in{x<3:a,m>2:A,R}
+            a{s>1:R,a<2:A,A}
+        "});
+        let mut expected = 0;
+        for x in 1..=4 {
+            for m in 1..=4 {
+                for a in 1..=4 {
+                    for s_ in 1..=4 {
+                        if s.accepts(&[x, m, a, s_]).unwrap() {
+                            expected += 1;
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(s.count_accepted(1..=4).unwrap(), expected);
+        assert_eq!(s.count_accepted(RangeInclusive::new(5, 4)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_count_overflow() {
+        // Every rating is accepted, so the count is the bound to the fourth.
+        let s = system("in{A}");
+        let max = u32::MAX as u64;
+        assert_eq!(s.count_accepted(1..=max).unwrap(), (max as u128).pow(4));
+        assert!(s.count_accepted(1..=10_000_000_000).is_err());
+    }
+
+    #[test]
+    fn test_loop() {
+        let s = system(indoc! {"
+            in{x<3:a,A}
+            a{in}
+        "});
+        assert!(s.accepts(&[1, 1, 1, 1]).is_err());
+        assert!(s.count_accepted(1..=4000).is_err());
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines())?, 19114);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines(), 1..=4000)?, 167409079868000);
+        Ok(())
+    }
 }
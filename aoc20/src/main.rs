@@ -1,9 +1,394 @@
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Broadcaster,
+    // On or off.
+    FlipFlop(bool),
+    // The most recent pulse from each input, in the same order as `inputs`.
+    Conjunction(Vec<bool>),
+    // Named as a destination but never declared, like `rx` or `output`.
+    Sink,
+}
+
+#[derive(Debug, Clone)]
+struct Module {
+    name: String,
+    kind: Kind,
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pulse {
+    from: usize,
+    to: usize,
+    high: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Circuit {
+    modules: Vec<Module>,
+    index: HashMap<String, usize>,
+    broadcaster: usize,
+}
+
+impl Circuit {
+    fn parse(lines: &[String]) -> Result<Circuit> {
+        let mut circuit = Circuit {
+            modules: Vec::new(),
+            index: HashMap::new(),
+            broadcaster: 0,
+        };
+        let mut declared = Vec::new();
+        for line in lines {
+            let (decl, outputs) = line
+                .split_once(" -> ")
+                .ok_or_else(|| anyhow!("Expected \"module -> outputs\", got {:?}", line))?;
+            let (kind, name) = match decl.chars().next() {
+                Some('%') => (Kind::FlipFlop(false), &decl[1..]),
+                Some('&') => (Kind::Conjunction(Vec::new()), &decl[1..]),
+                _ if decl == "broadcaster" => (Kind::Broadcaster, decl),
+                _ => return Err(anyhow!("Unknown module type {:?}", decl)),
+            };
+            let idx = circuit.module(name);
+            if circuit.modules[idx].kind != Kind::Sink {
+                return Err(anyhow!("Duplicate module {:?}", name));
+            }
+            circuit.modules[idx].kind = kind;
+            declared.push((idx, outputs));
+        }
+        for (idx, outputs) in declared {
+            for output in outputs.split(", ") {
+                let out = circuit.module(output);
+                circuit.modules[idx].outputs.push(out);
+                circuit.modules[out].inputs.push(idx);
+            }
+        }
+        for m in circuit.modules.iter_mut() {
+            if let Kind::Conjunction(memory) = &mut m.kind {
+                *memory = vec![false; m.inputs.len()];
+            }
+        }
+        circuit.broadcaster = *circuit
+            .index
+            .get("broadcaster")
+            .ok_or_else(|| anyhow!("No broadcaster"))?;
+        Ok(circuit)
+    }
+
+    // Looks up a module by name, adding it as a sink if it's new.
+    fn module(&mut self, name: &str) -> usize {
+        if let Some(idx) = self.index.get(name) {
+            return *idx;
+        }
+        self.modules.push(Module {
+            name: name.to_string(),
+            kind: Kind::Sink,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        });
+        self.index.insert(name.to_string(), self.modules.len() - 1);
+        self.modules.len() - 1
+    }
+
+    // Presses the button once, calling `observe` on every pulse sent, in order.
+    fn press(&mut self, mut observe: impl FnMut(&Pulse)) {
+        let mut queue = VecDeque::from([Pulse {
+            from: self.broadcaster,
+            to: self.broadcaster,
+            high: false,
+        }]);
+        while let Some(pulse) = queue.pop_front() {
+            observe(&pulse);
+            let module = &mut self.modules[pulse.to];
+            let send = match &mut module.kind {
+                Kind::Broadcaster => Some(pulse.high),
+                Kind::FlipFlop(on) => {
+                    if pulse.high {
+                        None
+                    } else {
+                        *on = !*on;
+                        Some(*on)
+                    }
+                }
+                Kind::Conjunction(memory) => {
+                    let input = module.inputs.iter().position(|i| *i == pulse.from).unwrap();
+                    memory[input] = pulse.high;
+                    Some(!memory.iter().all(|m| *m))
+                }
+                Kind::Sink => None,
+            };
+            if let Some(high) = send {
+                for to in &module.outputs {
+                    queue.push_back(Pulse {
+                        from: pulse.to,
+                        to: *to,
+                        high,
+                    });
+                }
+            }
+        }
+    }
+
+    // Low and high pulse counts over `presses` button presses.
+    fn count_pulses(&mut self, presses: usize) -> (u64, u64) {
+        let (mut low, mut high) = (0, 0);
+        for _ in 0..presses {
+            self.press(|p| if p.high { high += 1 } else { low += 1 });
+        }
+        (low, high)
+    }
+
+    // Fewest presses before `target` receives a low pulse. This relies on the
+    // usual input structure: `target` is fed by a single conjunction, each of
+    // whose inputs sends it a high pulse periodically, so the answer is the
+    // least common multiple of the periods. Each period is checked by waiting
+    // for a second high pulse, and up to `limit` presses are simulated.
+    fn presses_until_low(&mut self, target: &str, limit: usize) -> Result<u64> {
+        let target = *self
+            .index
+            .get(target)
+            .ok_or_else(|| anyhow!("No module named {:?}", target))?;
+        let feeder = match self.modules[target].inputs[..] {
+            [feeder] => feeder,
+            _ => {
+                return Err(anyhow!(
+                    "{:?} has {} inputs, expected a single conjunction",
+                    self.modules[target].name,
+                    self.modules[target].inputs.len()
+                ))
+            }
+        };
+        if !matches!(self.modules[feeder].kind, Kind::Conjunction(_)) {
+            return Err(anyhow!(
+                "{:?} feeds {:?} but is not a conjunction",
+                self.modules[feeder].name,
+                self.modules[target].name
+            ));
+        }
+        let inputs = self.modules[feeder].inputs.clone();
+        if inputs.is_empty() {
+            return Err(anyhow!(
+                "{:?} feeds {:?} but has no inputs",
+                self.modules[feeder].name,
+                self.modules[target].name
+            ));
+        }
+        let mut highs = vec![Vec::new(); inputs.len()];
+        for press in 1..=limit {
+            let mut low_to_target = false;
+            self.press(|p| {
+                if p.to == feeder && p.high {
+                    let i = inputs.iter().position(|i| *i == p.from).unwrap();
+                    if highs[i].last() != Some(&press) {
+                        highs[i].push(press);
+                    }
+                }
+                if p.to == target && !p.high {
+                    low_to_target = true;
+                }
+            });
+            if low_to_target {
+                return Ok(press as u64);
+            }
+            if highs.iter().all(|h| h.len() >= 2) {
+                break;
+            }
+        }
+        let mut result = 1;
+        for (input, h) in inputs.iter().zip(&highs) {
+            let name = &self.modules[*input].name;
+            match h[..] {
+                [first, second, ..] if second == 2 * first => result = lcm(result, first as u64),
+                [first, second, ..] => {
+                    return Err(anyhow!(
+                        "{:?} sent high pulses at presses {} and {}, which isn't periodic from the start",
+                        name,
+                        first,
+                        second
+                    ))
+                }
+                _ => {
+                    return Err(anyhow!(
+                        "{:?} didn't send two high pulses within {} presses",
+                        name,
+                        limit
+                    ))
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    a / gcd(a, b) * b
+}
+
+fn part1(lines: &[String]) -> Result<u64> {
+    let (low, high) = Circuit::parse(lines)?.count_pulses(1000);
+    Ok(low * high)
+}
+
+fn part2(lines: &[String]) -> Result<u64> {
+    Circuit::parse(lines)?.presses_until_low("rx", 100_000)
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|x| x.to_string()).collect()
+    }
+
+    fn example1() -> Vec<String> {
+        lines(indoc! {"
+            broadcaster -> a, b, c
+            %a -> b
+            %b -> c
+            %c -> inv
+            &inv -> a
+        "})
+    }
+
+    fn example2() -> Vec<String> {
+        lines(indoc! {"
+            broadcaster -> a
+            %a -> inv, con
+            &inv -> b
+            %b -> con
+            &con -> output
+        "})
+    }
+
+    // Three binary counters with periods 3, 5 and 7, built the way puzzle
+    // inputs are: a conjunction watches the bits that are set in the period,
+    // and once they're all on it resets the counter and pulses high, via an
+    // inverter, into the conjunction in front of rx.
+    fn counters() -> Vec<String> {
+        lines(indoc! {"
+            broadcaster -> q0, r0, s0
+            %q0 -> q1, cq
+            %q1 -> cq
+            &cq -> iq, q0
+            &iq -> hub
+            %r0 -> r1, cr
+            %r1 -> r2
+            %r2 -> cr
+            &cr -> ir, r0, r1
+            &ir -> hub
+            %s0 -> s1, cs
+            %s1 -> s2, cs
+            %s2 -> cs
+            &cs -> is, s0
+            &is -> hub
+            &hub -> rx
+        "})
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let c = Circuit::parse(&example2())?;
+        assert_eq!(c.modules.len(), 6);
+        let con = &c.modules[c.index["con"]];
+        assert_eq!(con.kind, Kind::Conjunction(vec![false, false]));
+        assert_eq!(c.modules[c.index["output"]].kind, Kind::Sink);
+        assert!(Circuit::parse(&lines("%a -> b")).is_err());
+        assert!(Circuit::parse(&lines("broadcaster -> a\n%a -> b\n&a -> b")).is_err());
+        assert!(Circuit::parse(&lines("broadcaster -> a\n?a -> b")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_press() -> Result<()> {
+        let mut c = Circuit::parse(&example1())?;
+        assert_eq!(c.count_pulses(1), (8, 4));
+        let mut c = Circuit::parse(&example2())?;
+        assert_eq!(c.count_pulses(4), (17, 11));
+        Ok(())
+    }
+
+    fn brute_force(lines: &[String], target: &str) -> Result<u64> {
+        let mut c = Circuit::parse(lines)?;
+        let target = c.index[target];
+        let mut presses = 0;
+        let mut done = false;
+        while !done {
+            presses += 1;
+            c.press(|p| done |= p.to == target && !p.high);
+        }
+        Ok(presses)
+    }
+
+    #[test]
+    fn test_presses_until_low() -> Result<()> {
+        let mut c = Circuit::parse(&counters())?;
+        assert_eq!(
+            c.presses_until_low("rx", 1000)?,
+            brute_force(&counters(), "rx")?
+        );
+        // Found by simulation before the periods are known.
+        let mut c = Circuit::parse(&example2())?;
+        assert_eq!(
+            c.presses_until_low("output", 1000)?,
+            brute_force(&example2(), "output")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnostics() -> Result<()> {
+        let mut c = Circuit::parse(&example2())?;
+        assert!(c.presses_until_low("rx", 1000).is_err());
+        let mut c = Circuit::parse(&lines("broadcaster -> a\n%a -> rx"))?;
+        assert!(c.presses_until_low("rx", 1000).is_err());
+        let mut c = Circuit::parse(&lines("broadcaster -> a, b\n%a -> rx\n%b -> rx"))?;
+        assert!(c.presses_until_low("rx", 1000).is_err());
+        // a pulses high at presses 1 and 3, so isn't periodic from the start,
+        // and z never pulses at all.
+        let mut c = Circuit::parse(&lines("broadcaster -> a\n%a -> con\n%z -> con\n&con -> rx"))?;
+        assert!(c.presses_until_low("rx", 1000).is_err());
+        // Nothing ever reaches hub, so rx never gets a pulse.
+        let mut c = Circuit::parse(&lines("broadcaster -> a\n%a -> b\n&hub -> rx"))?;
+        assert_eq!(
+            c.presses_until_low("rx", 1000).unwrap_err().to_string(),
+            "\"hub\" feeds \"rx\" but has no inputs"
+        );
+        Ok(())
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&example1())?, 32000000);
+        assert_eq!(part1(&example2())?, 11687500);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&counters())?, 105);
+        Ok(())
+    }
 }
@@ -1,9 +1,458 @@
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+// The most plots to search when extrapolating to the infinite garden, about
+// 64MB of distances.
+const MAX_PLOTS: usize = 4_000_000;
+
+struct Garden {
+    rocks: Vec<Vec<bool>>,
+    start: (usize, usize),
+}
+
+impl Garden {
+    fn parse(lines: &[String]) -> Result<Garden> {
+        let mut rocks = Vec::new();
+        let mut start = None;
+        for (r, line) in lines.iter().enumerate() {
+            let mut row = Vec::new();
+            for (c, ch) in line.chars().enumerate() {
+                match ch {
+                    '#' => row.push(true),
+                    '.' => row.push(false),
+                    'S' if start.is_none() => {
+                        start = Some((r, c));
+                        row.push(false);
+                    }
+                    'S' => return Err(anyhow!("More than one start")),
+                    _ => return Err(anyhow!("Unexpected character {:?} in {:?}", ch, line)),
+                }
+            }
+            rocks.push(row);
+        }
+        if rocks.is_empty() || rocks.iter().any(|row| row.len() != rocks[0].len()) {
+            return Err(anyhow!("Garden must be a non-empty rectangle"));
+        }
+        Ok(Garden {
+            rocks,
+            start: start.ok_or_else(|| anyhow!("No start"))?,
+        })
+    }
+
+    fn height(&self) -> usize {
+        self.rocks.len()
+    }
+
+    fn width(&self) -> usize {
+        self.rocks[0].len()
+    }
+
+    // Shortest distances from the start over `tiles` x `tiles` copies of the
+    // garden, with the start in the middle copy. Unreachable plots are None.
+    fn distances(&self, tiles: usize) -> Vec<Vec<Option<usize>>> {
+        let (h, w) = (self.height() * tiles, self.width() * tiles);
+        let mut dist = vec![vec![None; w]; h];
+        let start = (
+            tiles / 2 * self.height() + self.start.0,
+            tiles / 2 * self.width() + self.start.1,
+        );
+        dist[start.0][start.1] = Some(0);
+        let mut queue = VecDeque::from([start]);
+        while let Some((r, c)) = queue.pop_front() {
+            let d = dist[r][c].unwrap();
+            let mut neighbors = Vec::new();
+            if r > 0 {
+                neighbors.push((r - 1, c));
+            }
+            if r + 1 < h {
+                neighbors.push((r + 1, c));
+            }
+            if c > 0 {
+                neighbors.push((r, c - 1));
+            }
+            if c + 1 < w {
+                neighbors.push((r, c + 1));
+            }
+            for (nr, nc) in neighbors {
+                if dist[nr][nc].is_none() && !self.rocks[nr % self.height()][nc % self.width()] {
+                    dist[nr][nc] = Some(d + 1);
+                    queue.push_back((nr, nc));
+                }
+            }
+        }
+        dist
+    }
+
+    // Plots reachable in exactly `steps` steps without leaving the map. A plot
+    // at distance d is reachable if d <= steps and has the same parity, since
+    // the spare steps can be spent walking back and forth, unless the start is
+    // boxed in and there's nowhere to walk.
+    fn reachable(&self, steps: usize) -> usize {
+        let dist = self.distances(1);
+        if boxed_in(&dist) {
+            return (steps == 0) as usize;
+        }
+        dist.iter()
+            .flatten()
+            .flatten()
+            .filter(|d| **d <= steps && **d % 2 == steps % 2)
+            .count()
+    }
+
+    // Whether moving one tile outwards from the outermost ring of tiles adds
+    // exactly one tile width to every distance, judging by the ring inside it.
+    fn settled(dist: &[Vec<Option<usize>>], n: usize, tiles: usize) -> bool {
+        let outer = tiles - 1;
+        for (r, row) in dist.iter().enumerate() {
+            for (c, d) in row.iter().enumerate() {
+                let (tr, tc) = (r / n, c / n);
+                let mut inner = Vec::new();
+                match tr {
+                    0 => inner.push((r + n, c)),
+                    _ if tr == outer => inner.push((r - n, c)),
+                    _ => {}
+                }
+                match tc {
+                    0 => inner.push((r, c + n)),
+                    _ if tc == outer => inner.push((r, c - n)),
+                    _ => {}
+                }
+                for (ir, ic) in inner {
+                    if dist[ir][ic].map(|d| d + n) != *d {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // Distances over enough copies of the map to extrapolate to the
+    // infinitely tiled map. Since the map's border is clear, far enough out
+    // the shortest path to every plot runs along the lattice of borders, and
+    // moving one tile further out adds exactly one tile width to every
+    // distance. The BFS is widened until that holds for the outermost ring.
+    fn infinite(&self) -> Result<InfiniteGarden> {
+        if self.height() != self.width() {
+            return Err(anyhow!(
+                "Infinite garden must be square, got {}x{}",
+                self.height(),
+                self.width()
+            ));
+        }
+        let n = self.width();
+        if (0..n).any(|i| {
+            self.rocks[0][i] || self.rocks[n - 1][i] || self.rocks[i][0] || self.rocks[i][n - 1]
+        }) {
+            return Err(anyhow!("Infinite garden must have no rocks on its border"));
+        }
+        let mut radius = 2;
+        loop {
+            let tiles = 2 * radius + 1;
+            if (tiles * n).pow(2) > MAX_PLOTS {
+                return Err(anyhow!(
+                    "Distances didn't settle before the search outgrew {} plots",
+                    MAX_PLOTS
+                ));
+            }
+            let dist = self.distances(tiles);
+            if Garden::settled(&dist, n, tiles) {
+                return Ok(InfiniteGarden { n, tiles, dist });
+            }
+            if radius > n + 2 {
+                return Err(anyhow!("Distances didn't settle within {} tiles", radius));
+            }
+            radius += 1;
+        }
+    }
+}
+
+struct InfiniteGarden {
+    n: usize,
+    tiles: usize,
+    dist: Vec<Vec<Option<usize>>>,
+}
+
+impl InfiniteGarden {
+    // Plots reachable in exactly `steps` steps. Plots in the computed tiles
+    // are counted directly; each plot in an edge tile also stands for a line
+    // of tiles outwards, and each plot in a corner tile for a quadrant of
+    // tiles, counted in closed form.
+    fn reachable(&self, steps: usize) -> usize {
+        if boxed_in(&self.dist) {
+            return (steps == 0) as usize;
+        }
+        let (n, tiles) = (self.n, self.tiles);
+        let mut count = 0;
+        for (r, row) in self.dist.iter().enumerate() {
+            for (c, d) in row.iter().enumerate() {
+                let Some(d) = *d else {
+                    continue;
+                };
+                if d > steps {
+                    continue;
+                }
+                if d % 2 == steps % 2 {
+                    count += 1;
+                }
+                let edge_r = r / n == 0 || r / n == tiles - 1;
+                let edge_c = c / n == 0 || c / n == tiles - 1;
+                count += match (edge_r, edge_c) {
+                    (true, true) => quadrant(d, n, steps),
+                    (true, false) | (false, true) => line(d, n, steps),
+                    (false, false) => 0,
+                };
+            }
+        }
+        count
+    }
+}
+
+fn boxed_in(dist: &[Vec<Option<usize>>]) -> bool {
+    dist.iter().flatten().flatten().nth(1).is_none()
+}
+
+// Number of k >= 1 with d + k * n <= steps and the same parity as steps.
+fn line(d: usize, n: usize, steps: usize) -> usize {
+    let max = (steps - d) / n;
+    match (n % 2, (steps - d) % 2) {
+        (0, 0) => max,
+        (0, _) => 0,
+        (_, 0) => max / 2,
+        (_, _) => max.div_ceil(2),
+    }
+}
+
+// Like `line`, but there are m + 1 tiles at distance d + m * n in a quadrant.
+fn quadrant(d: usize, n: usize, steps: usize) -> usize {
+    let max = (steps - d) / n;
+    match (n % 2, (steps - d) % 2) {
+        // All m in 1..=max.
+        (0, 0) => max * (max + 1) / 2 + max,
+        (0, _) => 0,
+        // Even m in 2..=max: m = 2i for i in 1..=k.
+        (_, 0) => {
+            let k = max / 2;
+            k * (k + 1) + k
+        }
+        // Odd m in 1..=max: m = 2i - 1 for i in 1..=k.
+        (_, _) => {
+            let k = max.div_ceil(2);
+            k * k + k
+        }
+    }
+}
+
+fn part1(lines: &[String], steps: usize) -> Result<usize> {
+    Ok(Garden::parse(lines)?.reachable(steps))
+}
+
+fn part2(lines: &[String], steps: usize) -> Result<usize> {
+    Ok(Garden::parse(lines)?.infinite()?.reachable(steps))
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    println!("Part 1: {}", part1(&lines, 64)?);
+    println!("Part 2: {}", part2(&lines, 26501365)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            ...........
+            .....###.#.
+            .###.##..#.
+            ..#.#...#..
+            ....#.#....
+            .##..S####.
+            .##..#...#.
+            .......##..
+            .##.#.####.
+            .##..##.##.
+            ...........
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    fn garden(s: &str) -> Vec<String> {
+        s.lines().map(|x| x.to_string()).collect()
+    }
+
+    // Plots reachable after each number of steps up to `steps`.
+    fn brute_force(garden: &Garden, steps: usize) -> Vec<usize> {
+        let (h, w) = (garden.height() as i64, garden.width() as i64);
+        let mut frontier = HashSet::from([(garden.start.0 as i64, garden.start.1 as i64)]);
+        let mut counts = vec![1];
+        for _ in 0..steps {
+            let mut next = HashSet::new();
+            for (r, c) in frontier {
+                for (nr, nc) in [(r - 1, c), (r + 1, c), (r, c - 1), (r, c + 1)] {
+                    if !garden.rocks[nr.rem_euclid(h) as usize][nc.rem_euclid(w) as usize] {
+                        next.insert((nr, nc));
+                    }
+                }
+            }
+            frontier = next;
+            counts.push(frontier.len());
+        }
+        counts
+    }
+
+    #[test]
+    fn test_reachable() -> Result<()> {
+        let garden = Garden::parse(&lines())?;
+        assert_eq!(garden.reachable(6), 16);
+        Ok(())
+    }
+
+    #[test]
+    fn test_example_infinite() -> Result<()> {
+        let garden = Garden::parse(&lines())?.infinite()?;
+        for (steps, expected) in [(6, 16), (10, 50), (50, 1594), (100, 6536), (500, 167004)] {
+            assert_eq!(garden.reachable(steps), expected, "{} steps", steps);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_against_brute_force() -> Result<()> {
+        let gardens = [
+            lines(),
+            garden(indoc! {"
+                .....
+                .....
+                ..S..
+                .....
+                .....
+            "}),
+            garden(indoc! {"
+                .......
+                .#.....
+                .##....
+                ...S#..
+                ..#.#..
+                ...#.#.
+                .......
+            "}),
+            garden(indoc! {"
+                .........
+                ..#.#..#.
+                .....#S..
+                ..#.#....
+                .##..##..
+                .........
+                .........
+                ..#...##.
+                .........
+            "}),
+            garden(indoc! {"
+                ..........
+                ...#..#.#.
+                .....#....
+                .#.#....#.
+                ....#...#.
+                ..#.#S....
+                .....#....
+                ..#.##..#.
+                ..#..#....
+                ..........
+            "}),
+            garden(indoc! {"
+                .............
+                .#..#.#..##S.
+                .#.....#..##.
+                ..#...#......
+                .##...###....
+                ..##.#.....#.
+                ........#..#.
+                .#..#........
+                .###.####....
+                .....#....##.
+                .......##..#.
+                ....#.#...##.
+                .............
+            "}),
+            garden(indoc! {"
+                S.......
+                .#......
+                .##...#.
+                ..#..##.
+                ...##...
+                ....#.#.
+                ..#.#...
+                ........
+            "}),
+        ];
+        for (idx, lines) in gardens.iter().enumerate() {
+            let garden = Garden::parse(lines)?;
+            let infinite = garden.infinite()?;
+            for (steps, expected) in brute_force(&garden, 100).into_iter().enumerate() {
+                assert_eq!(
+                    infinite.reachable(steps),
+                    expected,
+                    "garden {} after {} steps",
+                    idx,
+                    steps
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported() -> Result<()> {
+        let garden = Garden::parse(&["..S..".to_string()])?;
+        assert!(garden.infinite().is_err());
+        assert_eq!(garden.reachable(2), 3);
+        let garden = Garden::parse(&["...".to_string(), ".S#".to_string(), "...".to_string()])?;
+        assert!(garden.infinite().is_err());
+        // Too big to search even two tiles out.
+        let mut big = vec![".".repeat(700); 700];
+        big[350] = format!("{}S{}", ".".repeat(350), ".".repeat(349));
+        let err = Garden::parse(&big)?.infinite().err().unwrap();
+        assert!(err.to_string().contains("didn't settle"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_boxed_in() -> Result<()> {
+        let garden = Garden::parse(&garden(indoc! {"
+            .....
+            .###.
+            .#S#.
+            .###.
+            .....
+        "}))?;
+        assert_eq!(garden.reachable(0), 1);
+        assert_eq!(garden.reachable(2), 0);
+        let infinite = garden.infinite()?;
+        assert_eq!(infinite.reachable(0), 1);
+        assert_eq!(infinite.reachable(2), 0);
+        Ok(())
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines(), 6)?, 16);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines(), 1000)?, 668697);
+        Ok(())
+    }
 }
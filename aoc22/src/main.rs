@@ -1,9 +1,307 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Brick {
+    min: [u32; 3],
+    max: [u32; 3],
+}
+
+impl Brick {
+    fn parse(line: &str) -> Result<Brick> {
+        let (a, b) = line
+            .split_once('~')
+            .ok_or_else(|| anyhow!("Expected \"x,y,z~x,y,z\", got {:?}", line))?;
+        let coords = |s: &str| -> Result<[u32; 3]> {
+            let v = s
+                .split(',')
+                .map(|x| x.parse::<u32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("Bad coordinate in {:?}: {}", line, e))?;
+            v.try_into()
+                .map_err(|_| anyhow!("Expected three coordinates in {:?}", line))
+        };
+        let (a, b) = (coords(a)?, coords(b)?);
+        let brick = Brick {
+            min: [0, 1, 2].map(|i| std::cmp::min(a[i], b[i])),
+            max: [0, 1, 2].map(|i| std::cmp::max(a[i], b[i])),
+        };
+        if brick.min[2] == 0 {
+            return Err(anyhow!("Brick {:?} is in the ground", line));
+        }
+        Ok(brick)
+    }
+
+    fn overlaps(&self, other: &Brick) -> bool {
+        (0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+
+    fn footprint(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (self.min[0]..=self.max[0])
+            .flat_map(move |x| (self.min[1]..=self.max[1]).map(move |y| (x, y)))
+    }
+}
+
+impl std::fmt::Display for Brick {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [x0, y0, z0] = self.min;
+        let [x1, y1, z1] = self.max;
+        write!(f, "{},{},{}~{},{},{}", x0, y0, z0, x1, y1, z1)
+    }
+}
+
+// The settled bricks, ordered by height so that every brick comes after the
+// bricks supporting it, with 0 standing for the ground.
+struct Stack {
+    bricks: Vec<Brick>,
+    supports: Vec<Vec<usize>>,
+    supported_by: Vec<Vec<usize>>,
+}
+
+impl Stack {
+    fn settle(mut bricks: Vec<Brick>) -> Result<Stack> {
+        bricks.sort_by_key(|b| b.min[2]);
+        for (i, brick) in bricks.iter().enumerate() {
+            if let Some(other) = bricks[..i].iter().find(|b| b.overlaps(brick)) {
+                return Err(anyhow!("Bricks {} and {} overlap", other, brick));
+            }
+        }
+        let n = bricks.len() + 1;
+        let mut supports = vec![Vec::new(); n];
+        let mut supported_by = vec![Vec::new(); n];
+        // For each (x, y), the height of the top of the column and which brick
+        // is there.
+        let mut heights: HashMap<(u32, u32), (u32, usize)> = HashMap::new();
+        for (i, brick) in bricks.iter_mut().enumerate() {
+            let id = i + 1;
+            let top = brick
+                .footprint()
+                .map(|xy| heights.get(&xy).map_or(0, |h| h.0))
+                .max()
+                .unwrap();
+            let mut below = brick
+                .footprint()
+                .map(|xy| *heights.get(&xy).unwrap_or(&(0, 0)))
+                .filter(|(h, _)| *h == top)
+                .map(|(_, b)| b)
+                .collect::<Vec<_>>();
+            below.sort();
+            below.dedup();
+            for b in below {
+                supports[b].push(id);
+                supported_by[id].push(b);
+            }
+            let fall = brick.min[2]
+                .checked_sub(top + 1)
+                .ok_or_else(|| anyhow!("Brick {} overlaps the bricks below it", brick))?;
+            brick.min[2] -= fall;
+            brick.max[2] -= fall;
+            for xy in brick.footprint() {
+                heights.insert(xy, (brick.max[2], id));
+            }
+        }
+        bricks.insert(
+            0,
+            Brick {
+                min: [0; 3],
+                max: [0; 3],
+            },
+        );
+        Ok(Stack {
+            bricks,
+            supports,
+            supported_by,
+        })
+    }
+
+    fn parse(lines: &[String]) -> Result<Stack> {
+        Stack::settle(
+            lines
+                .iter()
+                .map(|l| Brick::parse(l))
+                .collect::<Result<Vec<_>>>()?,
+        )
+    }
+
+    // Bricks that can be removed without any other brick falling.
+    fn safe_to_disintegrate(&self) -> Vec<usize> {
+        (1..self.bricks.len())
+            .filter(|b| {
+                self.supports[*b]
+                    .iter()
+                    .all(|s| self.supported_by[*s].len() > 1)
+            })
+            .collect()
+    }
+
+    // For each brick, how many other bricks would fall if it were removed.
+    //
+    // A brick falls exactly when every path down to the ground runs through
+    // the removed brick, i.e. when the removed brick dominates it in the
+    // support graph rooted at the ground. Since bricks are in height order,
+    // each brick's immediate dominator is the common ancestor, in the
+    // dominator tree built so far, of the bricks supporting it. The answer is
+    // then the size of each brick's subtree, less the brick itself.
+    fn chain_reactions(&self) -> Vec<usize> {
+        let n = self.bricks.len();
+        let mut idom = vec![0; n];
+        let mut depth = vec![0; n];
+        for b in 1..n {
+            let mut supporters = self.supported_by[b].iter().copied();
+            let mut d = supporters.next().unwrap();
+            for mut s in supporters {
+                while d != s {
+                    if depth[d] >= depth[s] {
+                        d = idom[d];
+                    } else {
+                        s = idom[s];
+                    }
+                }
+            }
+            idom[b] = d;
+            depth[b] = depth[d] + 1;
+        }
+        let mut size = vec![1; n];
+        for b in (1..n).rev() {
+            size[idom[b]] += size[b];
+        }
+        size[1..].iter().map(|s| s - 1).collect()
+    }
+}
+
+fn part1(lines: &[String]) -> Result<usize> {
+    Ok(Stack::parse(lines)?.safe_to_disintegrate().len())
+}
+
+fn part2(lines: &[String]) -> Result<usize> {
+    Ok(Stack::parse(lines)?.chain_reactions().iter().sum())
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            1,0,1~1,2,1
+            0,0,2~2,0,2
+            0,2,3~2,2,3
+            0,0,4~0,2,4
+            2,0,5~2,2,5
+            0,1,6~2,1,6
+            1,1,8~1,1,9
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    // Re-simulates the stack without `removed` and counts bricks that move.
+    fn resimulate(stack: &Stack, removed: usize) -> usize {
+        let mut fallen = HashSet::from([removed]);
+        for b in 1..stack.bricks.len() {
+            if stack.supported_by[b].iter().all(|s| fallen.contains(s)) {
+                fallen.insert(b);
+            }
+        }
+        fallen.len() - 1
+    }
+
+    #[test]
+    fn test_settle() -> Result<()> {
+        let stack = Stack::parse(&lines())?;
+        // A settles on the ground, B and C on A, and so on up to G.
+        assert_eq!(stack.bricks[1].min[2], 1);
+        assert_eq!(stack.bricks[7].min[2], 5);
+        assert_eq!(stack.bricks[7].max[2], 6);
+        assert_eq!(stack.supports[1], [2, 3]);
+        assert_eq!(stack.supported_by[4], [2, 3]);
+        assert_eq!(stack.supported_by[1], [0]);
+        assert_eq!(stack.safe_to_disintegrate(), [2, 3, 4, 5, 7]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_reactions() -> Result<()> {
+        let stack = Stack::parse(&lines())?;
+        assert_eq!(stack.chain_reactions(), [6, 0, 0, 0, 0, 1, 0]);
+
+        // A larger pile with every small brick shape at every position,
+        // checked against re-simulation. Far enough apart in z that upright
+        // bricks can't overlap.
+        let mut bricks = Vec::new();
+        for len in 0..3 {
+            for axis in 0..3 {
+                for y in 0..4 {
+                    for x in 0..4 {
+                        let z = 4 * bricks.len() as u32 + 1;
+                        let mut max = [x, y, z];
+                        max[axis] += len;
+                        bricks.push(Brick {
+                            min: [x, y, z],
+                            max,
+                        });
+                    }
+                }
+            }
+        }
+        let stack = Stack::settle(bricks)?;
+        let expected = (1..stack.bricks.len())
+            .map(|b| resimulate(&stack, b))
+            .collect::<Vec<_>>();
+        assert_eq!(stack.chain_reactions(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_overlap() {
+        let lines = ["0,0,1~0,0,5", "0,0,3~0,0,3"].map(|l| l.to_string());
+        assert_eq!(
+            Stack::parse(&lines).err().unwrap().to_string(),
+            "Bricks 0,0,1~0,0,5 and 0,0,3~0,0,3 overlap"
+        );
+        // The lower brick falls clear of the other, but they still overlap.
+        let lines = ["0,0,5~0,0,8", "1,0,6~0,0,6"].map(|l| l.to_string());
+        assert!(Stack::parse(&lines).is_err());
+        let apart = ["0,0,1~0,0,5", "0,1,3~1,1,3"].map(|l| l.to_string());
+        assert!(Stack::parse(&apart).is_ok());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(Brick::parse("1,0,1~1,2").is_err());
+        assert!(Brick::parse("1,0,0~1,2,0").is_err());
+        assert_eq!(
+            Brick::parse("1,2,5~1,0,1").unwrap(),
+            Brick {
+                min: [1, 0, 1],
+                max: [1, 2, 5]
+            }
+        );
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines())?, 5);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines())?, 7);
+        Ok(())
+    }
 }
@@ -1,9 +1,417 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+#[derive(Debug, Clone)]
+struct Edge {
+    to: usize,
+    // Every cell stepped on after leaving the junction, ending at `to`.
+    cells: Vec<(usize, usize)>,
+}
+
+impl Edge {
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+struct Map {
+    grid: Vec<Vec<char>>,
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+impl Map {
+    fn parse(lines: &[String]) -> Result<Map> {
+        let grid = lines
+            .iter()
+            .map(|l| l.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        if grid.len() < 2 || grid.iter().any(|row| row.len() != grid[0].len()) {
+            return Err(anyhow!("Map must be a rectangle at least two rows high"));
+        }
+        for row in &grid {
+            if let Some(c) = row
+                .iter()
+                .find(|c| !matches!(c, '#' | '.' | '^' | 'v' | '<' | '>'))
+            {
+                return Err(anyhow!("Unexpected character {:?}", c));
+            }
+        }
+        let find_gap = |r: usize| -> Result<(usize, usize)> {
+            match grid[r].iter().position(|c| *c == '.') {
+                Some(c) => Ok((r, c)),
+                None => Err(anyhow!("No gap in row {}", r)),
+            }
+        };
+        Ok(Map {
+            start: find_gap(0)?,
+            end: find_gap(grid.len() - 1)?,
+            grid,
+        })
+    }
+
+    fn open(&self, (r, c): (usize, usize)) -> bool {
+        self.grid[r][c] != '#'
+    }
+
+    // Open neighbors of a cell, with whether stepping there obeys any slope
+    // on the cell being left.
+    fn neighbors(&self, (r, c): (usize, usize)) -> Vec<((usize, usize), bool)> {
+        let mut v = Vec::new();
+        if r > 0 {
+            v.push(((r - 1, c), '^'));
+        }
+        if r + 1 < self.grid.len() {
+            v.push(((r + 1, c), 'v'));
+        }
+        if c > 0 {
+            v.push(((r, c - 1), '<'));
+        }
+        if c + 1 < self.grid[0].len() {
+            v.push(((r, c + 1), '>'));
+        }
+        v.into_iter()
+            .filter(|(p, _)| self.open(*p))
+            .map(|(p, dir)| {
+                let here = self.grid[r][c];
+                (p, here == '.' || here == dir)
+            })
+            .collect()
+    }
+
+    // Compresses the map into a graph of junctions: the start, the end, and
+    // every cell where paths meet. With `slopes`, corridors can only be walked
+    // downhill.
+    fn graph(&self, slopes: bool) -> Graph {
+        let mut junctions = vec![self.start, self.end];
+        for (r, row) in self.grid.iter().enumerate() {
+            for c in 0..row.len() {
+                if self.open((r, c)) && self.neighbors((r, c)).len() > 2 {
+                    junctions.push((r, c));
+                }
+            }
+        }
+        let index = junctions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (*p, i))
+            .collect::<HashMap<_, _>>();
+        let mut edges = vec![Vec::new(); junctions.len()];
+        for (from, junction) in junctions.iter().enumerate() {
+            for (first, allowed) in self.neighbors(*junction) {
+                if slopes && !allowed {
+                    continue;
+                }
+                let mut cells = vec![first];
+                let mut prev = *junction;
+                let mut ok = true;
+                while !index.contains_key(cells.last().unwrap()) {
+                    let here = *cells.last().unwrap();
+                    let next = self.neighbors(here).into_iter().find(|(p, _)| *p != prev);
+                    match next {
+                        Some((p, allowed)) if allowed || !slopes => {
+                            prev = here;
+                            cells.push(p);
+                        }
+                        // A dead end, or a slope the wrong way.
+                        _ => {
+                            ok = false;
+                            break;
+                        }
+                    }
+                }
+                if ok {
+                    edges[from].push(Edge {
+                        to: index[cells.last().unwrap()],
+                        cells,
+                    });
+                }
+            }
+        }
+        Graph { junctions, edges }
+    }
+
+    // Draws a hike on the map, marking the start `S` and each step `O`.
+    fn render(&self, graph: &Graph, path: &[usize]) -> String {
+        let mut grid = self.grid.clone();
+        grid[self.start.0][self.start.1] = 'S';
+        for pair in path.windows(2) {
+            if let Some(edge) = graph.edge(pair[0], pair[1]) {
+                for (r, c) in &edge.cells {
+                    grid[*r][*c] = 'O';
+                }
+            }
+        }
+        let mut s = String::new();
+        for row in grid {
+            s.extend(row);
+            s.push('\n');
+        }
+        s
+    }
+}
+
+const START: usize = 0;
+const END: usize = 1;
+
+struct Graph {
+    junctions: Vec<(usize, usize)>,
+    edges: Vec<Vec<Edge>>,
+}
+
+impl Graph {
+    // The longest edge between two junctions, if any.
+    fn edge(&self, from: usize, to: usize) -> Option<&Edge> {
+        self.edges[from]
+            .iter()
+            .filter(|e| e.to == to)
+            .max_by_key(|e| e.len())
+    }
+
+    // Longest path from start to end when the graph is acyclic, as it is when
+    // slopes are respected. Returns the length and the junctions visited.
+    fn longest_dag(&self) -> Result<(usize, Vec<usize>)> {
+        let n = self.junctions.len();
+        let mut indegree = vec![0; n];
+        for e in self.edges.iter().flatten() {
+            indegree[e.to] += 1;
+        }
+        let mut order = Vec::new();
+        let mut ready = (0..n).filter(|j| indegree[*j] == 0).collect::<Vec<_>>();
+        while let Some(j) = ready.pop() {
+            order.push(j);
+            for e in &self.edges[j] {
+                indegree[e.to] -= 1;
+                if indegree[e.to] == 0 {
+                    ready.push(e.to);
+                }
+            }
+        }
+        if order.len() != n {
+            return Err(anyhow!("Trails have a cycle"));
+        }
+        let mut prev = vec![None; n];
+        let mut dist = vec![None; n];
+        dist[START] = Some(0);
+        for j in order {
+            let Some(d) = dist[j] else {
+                continue;
+            };
+            for e in &self.edges[j] {
+                if dist[e.to].is_none_or(|old| d + e.len() > old) {
+                    dist[e.to] = Some(d + e.len());
+                    prev[e.to] = Some(j);
+                }
+            }
+        }
+        let len = dist[END].ok_or_else(|| anyhow!("No path to the end"))?;
+        let mut path = vec![END];
+        while let Some(p) = prev[*path.last().unwrap()] {
+            path.push(p);
+        }
+        path.reverse();
+        Ok((len, path))
+    }
+
+    // Longest simple path from start to end in any graph, by depth-first
+    // search over the set of visited junctions. Returns the length and the
+    // junctions visited.
+    fn longest(&self) -> Result<(usize, Vec<usize>)> {
+        let n = self.junctions.len();
+        if n > 64 {
+            return Err(anyhow!("Too many junctions: {}", n));
+        }
+        // The most a path can gain by entering each junction.
+        let max_in = (0..n)
+            .map(|j| {
+                self.edges
+                    .iter()
+                    .flatten()
+                    .filter(|e| e.to == j)
+                    .map(|e| e.len())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect::<Vec<_>>();
+        let mut search = Search {
+            graph: self,
+            max_in,
+            // Once at the only junction leading to the end, a path that
+            // doesn't go straight to the end can never reach it.
+            last: match self
+                .edges
+                .iter()
+                .enumerate()
+                .filter(|(_, es)| es.iter().any(|e| e.to == END))
+                .collect::<Vec<_>>()[..]
+            {
+                [(j, _)] => Some(j),
+                _ => None,
+            },
+            path: vec![START],
+            best: None,
+        };
+        let remaining = (0..n).map(|j| search.max_in[j]).sum::<usize>() - search.max_in[START];
+        search.dfs(START, 1 << START, 0, remaining);
+        search.best.ok_or_else(|| anyhow!("No path to the end"))
+    }
+}
+
+struct Search<'a> {
+    graph: &'a Graph,
+    max_in: Vec<usize>,
+    last: Option<usize>,
+    path: Vec<usize>,
+    best: Option<(usize, Vec<usize>)>,
+}
+
+impl Search<'_> {
+    // `remaining` bounds how much more the path can grow: the sum of
+    // `max_in` over unvisited junctions.
+    fn dfs(&mut self, at: usize, visited: u64, len: usize, remaining: usize) {
+        if at == END {
+            if self.best.as_ref().is_none_or(|(best, _)| len > *best) {
+                self.best = Some((len, self.path.clone()));
+            }
+            return;
+        }
+        if self
+            .best
+            .as_ref()
+            .is_some_and(|(best, _)| len + remaining <= *best)
+        {
+            return;
+        }
+        for e in &self.graph.edges[at] {
+            if visited & (1 << e.to) != 0 || (Some(at) == self.last && e.to != END) {
+                continue;
+            }
+            self.path.push(e.to);
+            self.dfs(
+                e.to,
+                visited | (1 << e.to),
+                len + e.len(),
+                remaining - self.max_in[e.to],
+            );
+            self.path.pop();
+        }
+    }
+}
+
+fn part1(lines: &[String]) -> Result<usize> {
+    Ok(Map::parse(lines)?.graph(true).longest_dag()?.0)
+}
+
+fn part2(lines: &[String]) -> Result<usize> {
+    Ok(Map::parse(lines)?.graph(false).longest()?.0)
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    if std::env::args().any(|a| a == "--render") {
+        let map = Map::parse(&lines)?;
+        for slopes in [true, false] {
+            let graph = map.graph(slopes);
+            let (_, path) = if slopes {
+                graph.longest_dag()?
+            } else {
+                graph.longest()?
+            };
+            println!("{}", map.render(&graph, &path));
+        }
+    }
+    println!("Part 1: {}", part1(&lines)?);
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            #.#####################
+            #.......#########...###
+            #######.#########.#.###
+            ###.....#.>.>.###.#.###
+            ###v#####.#v#.###.#.###
+            ###.>...#.#.#.....#...#
+            ###v###.#.#.#########.#
+            ###...#.#.#.......#...#
+            #####.#.#.#######.#.###
+            #.....#.#.#.......#...#
+            #.#####.#.#.#########v#
+            #.#...#...#...###...>.#
+            #.#.#v#######v###.###v#
+            #...#.>.#...>.>.#.###.#
+            #####v#.#.###v#.#.###.#
+            #.....#...#...#.#.#...#
+            #.#########.###.#.#.###
+            #...###...#...#...#.###
+            ###.###.#.###v#####v###
+            #...#...#.#.>.>.#.>.###
+            #.###.###.#.###.#.#v###
+            #.....###...###...#...#
+            #####################.#
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_graph() -> Result<()> {
+        let map = Map::parse(&lines())?;
+        let graph = map.graph(false);
+        assert_eq!(graph.junctions.len(), 9);
+        assert_eq!(graph.edges[START].len(), 1);
+        assert_eq!(graph.edges[START][0].len(), 15);
+        // Slopes make the graph directed.
+        let directed = map.graph(true);
+        assert_eq!(directed.edges[END].len(), 0);
+        assert!(directed.edges.iter().flatten().count() < graph.edges.iter().flatten().count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() -> Result<()> {
+        let map = Map::parse(&lines())?;
+        let graph = map.graph(true);
+        let (len, path) = graph.longest_dag()?;
+        let render = map.render(&graph, &path);
+        assert_eq!(render.matches('O').count(), len);
+        assert_eq!(render.lines().next().unwrap(), "#S#####################");
+        assert_eq!(render.lines().nth(1).unwrap(), "#OOOOOOO#########...###");
+        let graph = map.graph(false);
+        let (len, path) = graph.longest()?;
+        assert_eq!(map.render(&graph, &path).matches('O').count(), len);
+        assert_eq!(path.first(), Some(&START));
+        assert_eq!(path.last(), Some(&END));
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_path() -> Result<()> {
+        let map = Map::parse(&["#.#".to_string(), "#>.".to_string(), "#.#".to_string()])?;
+        assert!(map.graph(true).longest_dag().is_err());
+        assert!(map.graph(false).longest().is_ok());
+        Ok(())
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines())?, 94);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines())?, 154);
+        Ok(())
+    }
 }
@@ -1,9 +1,275 @@
+use std::ops::RangeInclusive;
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn main() {
+type Vec3 = [i128; 3];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hailstone {
+    pos: Vec3,
+    vel: Vec3,
+}
+
+impl Hailstone {
+    fn parse(line: &str) -> Result<Hailstone> {
+        let (pos, vel) = line
+            .split_once('@')
+            .ok_or_else(|| anyhow!("Expected \"px, py, pz @ vx, vy, vz\", got {:?}", line))?;
+        let vec3 = |s: &str| -> Result<Vec3> {
+            let v = s
+                .split(',')
+                .map(|x| x.trim().parse::<i128>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow!("Bad number in {:?}: {}", line, e))?;
+            v.try_into()
+                .map_err(|_| anyhow!("Expected three numbers in {:?}", line))
+        };
+        Ok(Hailstone {
+            pos: vec3(pos)?,
+            vel: vec3(vel)?,
+        })
+    }
+
+    // Whether the future paths of two hailstones cross within `area`, ignoring
+    // the z axis. Everything is kept as exact integer numerators over the
+    // common denominator `det`, so there's no rounding near the boundaries.
+    fn paths_cross(&self, other: &Hailstone, area: &RangeInclusive<i128>) -> bool {
+        let (p1, v1, p2, v2) = (self.pos, self.vel, other.pos, other.vel);
+        let det = v1[0] * v2[1] - v1[1] * v2[0];
+        if det == 0 {
+            // Parallel paths never cross at a single point.
+            return false;
+        }
+        let (dx, dy) = (p2[0] - p1[0], p2[1] - p1[1]);
+        // Times at which each hailstone reaches the crossing are t / det and
+        // s / det.
+        let t = dx * v2[1] - dy * v2[0];
+        let s = dx * v1[1] - dy * v1[0];
+        // Normalize so the denominator is positive.
+        let (det, t, s) = if det < 0 { (-det, -t, -s) } else { (det, t, s) };
+        if t < 0 || s < 0 {
+            return false;
+        }
+        let x = p1[0] * det + t * v1[0];
+        let y = p1[1] * det + t * v1[1];
+        let (min, max) = (area.start() * det, area.end() * det);
+        (min..=max).contains(&x) && (min..=max).contains(&y)
+    }
+
+    // Whether `self`, treated as the rock, hits `other` at some time t >= 0.
+    fn hits(&self, other: &Hailstone) -> bool {
+        let dp = sub(other.pos, self.pos);
+        let dv = sub(self.vel, other.vel);
+        let mut time = None;
+        for i in 0..3 {
+            if dv[i] == 0 {
+                if dp[i] != 0 {
+                    return false;
+                }
+            } else if dp[i] % dv[i] != 0 || time.is_some_and(|t| t != dp[i] / dv[i]) {
+                return false;
+            } else {
+                time = Some(dp[i] / dv[i]);
+            }
+        }
+        time.unwrap_or(0) >= 0
+    }
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: Vec3, b: Vec3) -> Option<Vec3> {
+    let term = |i: usize, j: usize| a[i].checked_mul(b[j])?.checked_sub(a[j].checked_mul(b[i])?);
+    Some([term(1, 2)?, term(2, 0)?, term(0, 1)?])
+}
+
+fn dot(a: Vec3, b: Vec3) -> Option<i128> {
+    a.iter()
+        .zip(b)
+        .try_fold(0_i128, |acc, (x, y)| acc.checked_add(x.checked_mul(y)?))
+}
+
+fn exact_div(a: i128, b: i128) -> Option<i128> {
+    if b != 0 && a % b == 0 {
+        Some(a / b)
+    } else {
+        None
+    }
+}
+
+// Finds the rock that hits `h1` and `h2` after passing through `h0`.
+//
+// In the frame where `h0` is at rest at the origin, the rock's path passes
+// through the origin and crosses the paths of `h1` and `h2`, so it lies in the
+// plane through the origin containing `h1`'s path, with normal n1. `h2` meets
+// that plane when (p2 + t2 v2) . n1 = 0, which is linear in t2, and likewise
+// for t1. From the two collisions the rock's velocity and start follow. All
+// divisions must be exact, since the answer is integral.
+fn solve_rock(h0: &Hailstone, h1: &Hailstone, h2: &Hailstone) -> Option<Hailstone> {
+    let rel = |h: &Hailstone| (sub(h.pos, h0.pos), sub(h.vel, h0.vel));
+    let ((p1, v1), (p2, v2)) = (rel(h1), rel(h2));
+    let n1 = cross(p1, v1)?;
+    let n2 = cross(p2, v2)?;
+    let t2 = exact_div(-dot(p2, n1)?, dot(v2, n1)?)?;
+    let t1 = exact_div(-dot(p1, n2)?, dot(v1, n2)?)?;
+    if t1 == t2 {
+        return None;
+    }
+    let at = |h: &Hailstone, t: i128| -> Option<Vec3> {
+        let [x, y, z] = [0, 1, 2].map(|i| h.pos[i].checked_add(h.vel[i].checked_mul(t)?));
+        Some([x?, y?, z?])
+    };
+    let (c1, c2) = (at(h1, t1)?, at(h2, t2)?);
+    let mut vel = [0; 3];
+    let mut pos = [0; 3];
+    for i in 0..3 {
+        vel[i] = exact_div(c2[i] - c1[i], t2 - t1)?;
+        pos[i] = c1[i].checked_sub(vel[i].checked_mul(t1)?)?;
+    }
+    Some(Hailstone { pos, vel })
+}
+
+// The rock that hits every hailstone, solved from the first triple of
+// hailstones that determines it and then checked against all of them.
+fn find_rock(hail: &[Hailstone]) -> Result<Hailstone> {
+    for i in 0..hail.len() {
+        for j in i + 1..hail.len() {
+            for k in j + 1..hail.len() {
+                if let Some(rock) = solve_rock(&hail[i], &hail[j], &hail[k]) {
+                    return match hail.iter().find(|h| !rock.hits(h)) {
+                        None => Ok(rock),
+                        Some(h) => Err(anyhow!("Rock {:?} misses hailstone {:?}", rock, h)),
+                    };
+                }
+            }
+        }
+    }
+    Err(anyhow!("No three hailstones determine the rock"))
+}
+
+fn parse(lines: &[String]) -> Result<Vec<Hailstone>> {
+    lines.iter().map(|l| Hailstone::parse(l)).collect()
+}
+
+fn part1(lines: &[String], area: RangeInclusive<i128>) -> Result<usize> {
+    let hail = parse(lines)?;
+    let mut count = 0;
+    for (i, a) in hail.iter().enumerate() {
+        for b in &hail[i + 1..] {
+            if a.paths_cross(b, &area) {
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+fn part2(lines: &[String]) -> Result<i128> {
+    let rock = find_rock(&parse(lines)?)?;
+    Ok(rock.pos.iter().sum())
+}
+
+fn main() -> Result<()> {
     let lines = input_lines();
+    println!(
+        "Part 1: {}",
+        part1(&lines, 200000000000000..=400000000000000)?
+    );
+    println!("Part 2: {}", part2(&lines)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    fn lines() -> Vec<String> {
+        indoc! {"
+            19, 13, 30 @ -2,  1, -2
+            18, 19, 22 @ -1, -1, -2
+            20, 25, 34 @ -2, -2, -4
+            12, 31, 28 @ -1, -2, -1
+            20, 19, 15 @  1, -5, -3
+        "}
+        .lines()
+        .map(|x| x.to_string())
+        .collect()
+    }
+
+    fn hailstone(s: &str) -> Hailstone {
+        Hailstone::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_paths_cross() {
+        let a = hailstone("19, 13, 30 @ -2, 1, -2");
+        assert!(a.paths_cross(&hailstone("18, 19, 22 @ -1, -1, -2"), &(7..=27)));
+        assert!(!a.paths_cross(&hailstone("20, 19, 15 @ 1, -5, -3"), &(7..=27)));
+        assert!(!hailstone("18, 19, 22 @ -1, -1, -2")
+            .paths_cross(&hailstone("20, 25, 34 @ -2, -2, -4"), &(7..=27)));
+    }
+
+    #[test]
+    fn test_boundary() {
+        // The paths cross at exactly (10, 10) and at (10 + 1/3, 10).
+        let a = hailstone("0, 10, 0 @ 1, 0, 0");
+        let b = hailstone("10, 0, 0 @ 0, 1, 0");
+        assert!(a.paths_cross(&b, &(10..=20)));
+        assert!(!a.paths_cross(&b, &(11..=20)));
+        let c = hailstone("10, 0, 0 @ 1, 30, 0");
+        assert!(!a.paths_cross(&c, &(0..=10)));
+        assert!(a.paths_cross(&c, &(0..=11)));
+        // Crossing in the past doesn't count.
+        assert!(!a.paths_cross(&hailstone("10, 20, 0 @ 0, 1, 0"), &(0..=100)));
+    }
+
+    #[test]
+    fn test_find_rock() -> Result<()> {
+        let rock = find_rock(&parse(&lines())?)?;
+        assert_eq!(
+            rock,
+            Hailstone {
+                pos: [24, 13, 10],
+                vel: [-3, 1, 2]
+            }
+        );
+        // Large coordinates like the real input stay exact.
+        let rock = Hailstone {
+            pos: [123456789012345, 234567890123456, 345678901234567],
+            vel: [-123, 45, 67],
+        };
+        let hail = [
+            ([211, -301, 107], 631221771251),
+            ([-17, 99, 23], 912345678901),
+            ([5, 7, -211], 413217659876),
+            ([-250, -45, 88], 777777777777),
+        ]
+        .map(|(vel, t): (Vec3, i128)| Hailstone {
+            pos: [0, 1, 2].map(|i| rock.pos[i] + (rock.vel[i] - vel[i]) * t),
+            vel,
+        });
+        assert_eq!(find_rock(&hail)?, rock);
+        // Moving one hailstone makes it impossible.
+        let mut missed = hail;
+        missed[3].pos[0] += 1;
+        assert!(find_rock(&missed).is_err());
+        Ok(())
+    }
 
-    println!("Part 1: {}", todo!());
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&lines(), 7..=27)?, 2);
+        Ok(())
+    }
 
-    println!("Part 2: {}", todo!());
+    #[test]
+    fn test_part2() -> Result<()> {
+        assert_eq!(part2(&lines())?, 47);
+        Ok(())
+    }
 }
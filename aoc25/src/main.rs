@@ -1,18 +1,156 @@
-use anyhow::Result;
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{anyhow, Result};
 use util::input_lines;
 
-fn part1(lines: &Vec<String>) -> Result<u32> {
-    Ok(0)
+#[derive(Debug, Clone)]
+struct Graph {
+    names: Vec<String>,
+    edges: Vec<(usize, usize)>,
+    // For each component, its neighbors and the edge leading to each.
+    adj: Vec<Vec<(usize, usize)>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cut {
+    edges: Vec<(String, String)>,
+    sizes: (usize, usize),
+}
+
+impl Graph {
+    fn parse(lines: &[String]) -> Result<Graph> {
+        let mut graph = Graph {
+            names: Vec::new(),
+            edges: Vec::new(),
+            adj: Vec::new(),
+        };
+        let mut index = HashMap::new();
+        let mut node = |graph: &mut Graph, name: &str| -> usize {
+            *index.entry(name.to_string()).or_insert_with(|| {
+                graph.names.push(name.to_string());
+                graph.adj.push(Vec::new());
+                graph.names.len() - 1
+            })
+        };
+        for line in lines {
+            let (from, to) = line
+                .split_once(": ")
+                .ok_or_else(|| anyhow!("Expected \"name: names\", got {:?}", line))?;
+            let a = node(&mut graph, from);
+            for name in to.split_whitespace() {
+                let b = node(&mut graph, name);
+                if a == b {
+                    return Err(anyhow!("{:?} is wired to itself", name));
+                }
+                if graph.adj[a].iter().any(|(n, _)| *n == b) {
+                    return Err(anyhow!("Duplicate wire {}/{}", from, name));
+                }
+                graph.adj[a].push((b, graph.edges.len()));
+                graph.adj[b].push((a, graph.edges.len()));
+                graph.edges.push((a, b));
+            }
+        }
+        Ok(graph)
+    }
+
+    // Pushes up to `limit` units of flow from `s` to `t`, with each wire
+    // carrying at most one unit in either direction. Returns the flow and the
+    // components still reachable from `s` in the residual graph, which form
+    // one side of a minimum cut if the flow is below `limit`.
+    fn max_flow(&self, s: usize, t: usize, limit: usize) -> (usize, Vec<bool>) {
+        // +1 if a unit flows along the edge as written, -1 if it flows back.
+        let mut flow = vec![0_i8; self.edges.len()];
+        let mut total = 0;
+        loop {
+            let mut prev = vec![None; self.names.len()];
+            let mut seen = vec![false; self.names.len()];
+            seen[s] = true;
+            let mut queue = VecDeque::from([s]);
+            while let Some(u) = queue.pop_front() {
+                for &(v, e) in &self.adj[u] {
+                    let forward = self.edges[e].0 == u;
+                    let open = if forward { flow[e] < 1 } else { flow[e] > -1 };
+                    if open && !seen[v] {
+                        seen[v] = true;
+                        prev[v] = Some((u, e));
+                        queue.push_back(v);
+                    }
+                }
+            }
+            if !seen[t] || total == limit {
+                return (total, seen);
+            }
+            let mut v = t;
+            while let Some((u, e)) = prev[v] {
+                flow[e] += if self.edges[e].0 == u { 1 } else { -1 };
+                v = u;
+            }
+            total += 1;
+        }
+    }
+
+    // A cut of exactly `size` wires splitting the components into two groups
+    // of more than one each, found by computing the maximum flow between
+    // pairs of components until one is the right size. A wire to a component
+    // with `size` or fewer wires would otherwise do, so cuts that leave one
+    // component on its own are skipped.
+    fn cut(&self, size: usize) -> Result<Cut> {
+        let n = self.names.len();
+        for s in 0..n {
+            for t in s + 1..n {
+                let (flow, side) = self.max_flow(s, t, size + 1);
+                let inside = side.iter().filter(|s| **s).count();
+                if flow != size || inside < 2 || n - inside < 2 {
+                    continue;
+                }
+                let mut edges = self
+                    .edges
+                    .iter()
+                    .filter(|(a, b)| side[*a] != side[*b])
+                    .map(|(a, b)| {
+                        let (a, b) = (self.names[*a].clone(), self.names[*b].clone());
+                        if a < b {
+                            (a, b)
+                        } else {
+                            (b, a)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                edges.sort();
+                return Ok(Cut {
+                    edges,
+                    sizes: (inside, n - inside),
+                });
+            }
+        }
+        Err(anyhow!(
+            "No cut of {} wires into two groups of more than one component",
+            size
+        ))
+    }
+}
+
+impl Cut {
+    fn product(&self) -> usize {
+        self.sizes.0 * self.sizes.1
+    }
 }
 
-fn part2(lines: &Vec<String>) -> Result<u32> {
-    Ok(0)
+// The three wires to disconnect, whose group sizes multiply to the answer.
+fn part1(lines: &[String]) -> Result<Cut> {
+    Graph::parse(lines)?.cut(3)
 }
 
 fn main() -> Result<()> {
     let lines = input_lines();
-    println!("Part 1: {}", part1(&lines)?);
-    println!("Part 2: {}", part2(&lines)?);
+    let cut = part1(&lines)?;
+    let wires = cut
+        .edges
+        .iter()
+        .map(|(a, b)| format!("{}/{}", a, b))
+        .collect::<Vec<_>>();
+    println!("Cut: {}", wires.join(", "));
+    println!("Part 1: {}", cut.product());
     Ok(())
 }
 
@@ -22,24 +160,86 @@ mod tests {
 
     use super::*;
 
-    fn lines() -> Vec<String> {
-        indoc! {"
-            // TODO: example input
-        "}
-        .lines()
-        .map(|x| x.to_string())
-        .collect()
+    fn lines(s: &str) -> Vec<String> {
+        s.lines().map(|x| x.to_string()).collect()
+    }
+
+    fn example() -> Vec<String> {
+        lines(indoc! {"
+            jqt: rhn xhk nvd
+            rsh: frs pzl lsr
+            xhk: hfx
+            cmg: qnr nvd lhk bvb
+            rhn: xhk bvb hfx
+            bvb: xhk hfx
+            pzl: lsr hfx nvd
+            qnr: nvd
+            ntq: jqt hfx bvb xhk
+            nvd: lhk
+            lsr: lhk
+            rzs: qnr cmg lsr rsh
+            frs: qnr lhk lsr
+        "})
     }
 
     #[test]
-    fn test_part1() -> Result<()> {
-        assert_eq!(part1(&lines())?, 0);
+    fn test_parse() -> Result<()> {
+        let graph = Graph::parse(&example())?;
+        assert_eq!(graph.names.len(), 15);
+        assert_eq!(graph.edges.len(), 33);
+        assert!(Graph::parse(&lines("a b c")).is_err());
+        assert!(Graph::parse(&lines("a: a")).is_err());
+        assert!(Graph::parse(&lines("a: b\nb: a")).is_err());
         Ok(())
     }
 
     #[test]
-    fn test_part2() -> Result<()> {
-        assert_eq!(part2(&lines())?, 0);
+    fn test_cut() -> Result<()> {
+        let cut = Graph::parse(&example())?.cut(3)?;
+        let edges = [("hfx", "pzl"), ("bvb", "cmg"), ("jqt", "nvd")]
+            .map(|(a, b)| (a.to_string(), b.to_string()));
+        let mut expected = edges.to_vec();
+        expected.sort();
+        assert_eq!(cut.edges, expected);
+        assert_eq!(cut.product(), 54);
+
+        // Four wires only cut off a single component of a complete graph.
+        let k5 = lines("a: b c d e\nb: c d e\nc: d e\nd: e");
+        assert!(Graph::parse(&k5)?.cut(3).is_err());
+        assert!(Graph::parse(&k5)?.cut(4).is_err());
+        assert!(Graph::parse(&lines("a: b"))?.cut(0).is_err());
+        assert!(Graph::parse(&[])?.cut(3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trivial_cuts() -> Result<()> {
+        // Two complete graphs on five joined by three wires, listed after a
+        // component with a single wire or with three.
+        let halves = indoc! {"
+            a2: a3 a4 a5
+            a3: a4 a5
+            a4: a5
+            b1: b2 b3 b4 b5
+            b2: b3 b4 b5
+            b3: b4 b5
+            b4: b5
+            a1: b1
+            a2: b2
+            a3: b3
+        "};
+        let pendant = lines(&format!("a1: leaf a2 a3 a4 a5\n{}", halves));
+        let cut = Graph::parse(&pendant)?.cut(3)?;
+        assert_eq!(cut.edges.len(), 3);
+        assert_eq!(cut.product(), 30);
+        let degree3 = lines(&format!("x: a1 a2 a3\na1: a2 a3 a4 a5\n{}", halves));
+        assert_eq!(Graph::parse(&degree3)?.cut(3)?.product(), 30);
+        Ok(())
+    }
+
+    #[test]
+    fn test_part1() -> Result<()> {
+        assert_eq!(part1(&example())?.product(), 54);
         Ok(())
     }
 }
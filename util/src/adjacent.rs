@@ -7,11 +7,11 @@ impl<T> Adjacent for Vec<Vec<T>> {
         let mut adj = Vec::new();
         if !self.is_empty() && !self[0].is_empty() {
             for rdelta in [-1, 0, 1] {
-                if (rdelta == -1 && r <= 0) || (rdelta == 1 && r >= self.len() - 1) {
+                if (rdelta == -1 && r == 0) || (rdelta == 1 && r >= self.len() - 1) {
                     continue;
                 }
                 for cdelta in [-1, 0, 1] {
-                    if (cdelta == -1 && c <= 0)
+                    if (cdelta == -1 && c == 0)
                         || (cdelta == 1 && c >= self[0].len() - 1)
                         || (rdelta == 0 && cdelta == 0)
                     {